@@ -0,0 +1,237 @@
+//! Compares rebuilding the [`CollisionGrid`] from scratch with updating it incrementally
+//! and the different [`Broadphase`]s with each other on the same workloads.
+//!
+//! Run with `cargo run --release --bin collision_bench`.
+
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+use last_stand::{
+    FIXED_DELTA,
+    ecs::entity::{Entity, World},
+    game::{
+        collision::{
+            Broadphase, Collider, Shape,
+            grid::{CollisionGrid, CollisionGridParams},
+            layer::CollisionLayer,
+        },
+        transform::Transform,
+    },
+};
+use macroquad::{
+    math::{Rect, Vec2},
+    rand::{gen_range, srand},
+};
+use strum::IntoEnumIterator;
+
+const COLLIDER_COUNTS: [usize; 3] = [1_000, 5_000, 20_000];
+const TICKS: u32 = 300;
+const WALK_SPEED: f32 = 170.;
+/// The multiples of the [`WALK_SPEED`] the grid updates are measured at,
+/// since the faster the colliders move, the more of them change their cells.
+const SPEED_FACTORS: [f32; 3] = [1., 2., 4.];
+
+/// Colliders moving around the map.
+struct Workload {
+    world: World,
+    velocities: Vec<Vec2>,
+    rect: Rect,
+}
+
+impl Workload {
+    /// Spawns `count` colliders keeping the density roughly the same as in the game.
+    ///
+    /// If `is_clustered` is `true`, half of the colliders are spawned
+    /// in the small area in the middle of the map.
+    fn new(count: usize, is_clustered: bool) -> Self {
+        let side = 1200. * (count as f32 / 100.).sqrt();
+        let rect = Rect::new(-side / 2., -side / 2., side, side);
+        let cluster = Rect::new(-side / 20., -side / 20., side / 10., side / 10.);
+        let mut world = World::new();
+        world.register_type::<Transform>();
+        world.register_type::<Collider>();
+        let mut velocities = Vec::with_capacity(count);
+        for index in 0..count {
+            let area = if is_clustered && index % 2 == 0 {
+                cluster
+            } else {
+                rect
+            };
+            let entity = world.create_entity();
+            {
+                let mut transforms = world.borrow_pool_mut::<Transform>();
+                let mut colliders = world.borrow_pool_mut::<Collider>();
+                transforms.insert(
+                    entity,
+                    Transform {
+                        position: Vec2::new(
+                            gen_range(area.left(), area.right()),
+                            gen_range(area.top(), area.bottom()),
+                        ),
+                        rotation: 0.,
+                    },
+                );
+                colliders.insert(
+                    entity,
+                    Collider {
+                        shape: Shape::Circle {
+                            radius: gen_range(10., 30.),
+                        },
+                        monitorable: CollisionLayer::Enemy.mask(),
+                        monitoring: CollisionLayer::Player.mask(),
+                        body: None,
+                    },
+                );
+            }
+            velocities.push(Vec2::from_angle(gen_range(0., std::f32::consts::TAU)) * WALK_SPEED);
+        }
+        Self {
+            world,
+            velocities,
+            rect,
+        }
+    }
+    /// Moves the colliders for one tick, bouncing them off the edges of the map.
+    fn step(&mut self) {
+        let mut transforms = self.world.borrow_pool_mut::<Transform>();
+        for ((_, transform), velocity) in transforms.iter_mut().zip(self.velocities.iter_mut()) {
+            transform.position += *velocity * FIXED_DELTA;
+            if !self.rect.contains(transform.position) {
+                *velocity = -*velocity;
+            }
+        }
+    }
+}
+
+fn main() {
+    srand(0);
+    println!("Grid updates (us per tick):");
+    println!(
+        "{:>10} {:>6} {:>12} {:>8} {:>12} {:>12}",
+        "colliders", "speed", "resolution", "moved", "rebuild", "incremental"
+    );
+    for count in COLLIDER_COUNTS {
+        for speed_factor in SPEED_FACTORS {
+            let (resolution, moved_fraction, rebuild, incremental) =
+                bench_grid_updates(count, speed_factor);
+            println!(
+                "{:>10} {:>6} {:>12} {:>7.1}% {:>12.1} {:>12.1}",
+                count,
+                format!("x{speed_factor}"),
+                format!("{}x{}", resolution.0, resolution.1),
+                moved_fraction * 100.,
+                micros_per_tick(rebuild),
+                micros_per_tick(incremental),
+            );
+        }
+    }
+    for is_clustered in [false, true] {
+        println!();
+        println!(
+            "Broadphases on {} colliders, update + pairs (us per tick):",
+            if is_clustered { "clustered" } else { "uniform" }
+        );
+        print!("{:>10}", "colliders");
+        for broadphase in Broadphase::iter() {
+            print!(" {:>16}", broadphase.to_string());
+        }
+        println!();
+        for count in COLLIDER_COUNTS {
+            print!("{count:>10}");
+            for broadphase in Broadphase::iter() {
+                let time = bench_broadphase(count, is_clustered, broadphase);
+                print!(" {:>16.1}", micros_per_tick(time));
+            }
+            println!();
+        }
+    }
+}
+
+fn micros_per_tick(time: Duration) -> f64 {
+    time.as_secs_f64() * 1e6 / TICKS as f64
+}
+
+/// Moves `count` colliders around for [`TICKS`] ticks at the [`WALK_SPEED`] multiplied
+/// by `speed_factor` and returns the chosen resolution, the average fraction of the colliders
+/// changing their cells every tick and the total time spent rebuilding and incrementally
+/// updating the grid.
+fn bench_grid_updates(
+    count: usize,
+    speed_factor: f32,
+) -> ((usize, usize), f32, Duration, Duration) {
+    let mut workload = Workload::new(count, false);
+    for velocity in &mut workload.velocities {
+        *velocity *= speed_factor;
+    }
+    let params =
+        CollisionGridParams::auto(workload.rect, &workload.world.borrow_pool::<Collider>());
+    let mut rebuilt = CollisionGrid::new();
+    let mut incremental = CollisionGrid::new();
+    let mut rebuild_time = Duration::ZERO;
+    let mut incremental_time = Duration::ZERO;
+    let mut moved_fraction = 0.;
+    for tick in 0..TICKS {
+        workload.step();
+        let transforms = workload.world.borrow_pool::<Transform>();
+        let colliders = workload.world.borrow_pool::<Collider>();
+
+        let start = Instant::now();
+        rebuilt.rebuild(&transforms, &colliders, params);
+        rebuild_time += start.elapsed();
+        // Every collider is new during the first tick.
+        if tick > 0 {
+            moved_fraction += rebuilt.moved_fraction() / (TICKS - 1) as f32;
+        }
+
+        let start = Instant::now();
+        incremental.update_incremental(&transforms, &colliders, params);
+        incremental_time += start.elapsed();
+    }
+    assert_same_cells(&rebuilt, &incremental);
+    (
+        params.resolution,
+        moved_fraction,
+        rebuild_time,
+        incremental_time,
+    )
+}
+
+/// Moves `count` colliders around for [`TICKS`] ticks and returns the total time
+/// spent updating the broadphase and finding the candidate pairs.
+fn bench_broadphase(count: usize, is_clustered: bool, broadphase: Broadphase) -> Duration {
+    let mut workload = Workload::new(count, is_clustered);
+    let mut index = broadphase.build();
+    let mut pairs = Vec::new();
+    let mut time = Duration::ZERO;
+    for _ in 0..TICKS {
+        workload.step();
+        let transforms = workload.world.borrow_pool::<Transform>();
+        let colliders = workload.world.borrow_pool::<Collider>();
+
+        let start = Instant::now();
+        index.update(&transforms, &colliders, workload.rect);
+        pairs.clear();
+        index.candidate_pairs(&mut pairs);
+        time += start.elapsed();
+    }
+    time
+}
+
+/// Panics if the two grids don't have the same entities in each of their cells.
+fn assert_same_cells(grid: &CollisionGrid, other_grid: &CollisionGrid) {
+    assert_eq!(grid.entry_count(), other_grid.entry_count());
+    let (width, height) = grid.params().resolution;
+    for y in 0..height {
+        for x in 0..width {
+            let (cell, other_cell) = (grid.cell(x, y), other_grid.cell(x, y));
+            assert!(
+                cell.len() == other_cell.len()
+                    && HashSet::<Entity>::from_iter(cell.iter().copied())
+                        == HashSet::from_iter(other_cell.iter().copied()),
+                "The incremental grid diverged from the rebuilt one!"
+            );
+        }
+    }
+}
@@ -64,6 +64,21 @@ impl<T> ComponentPool<T> {
         self.index_lookup[entity.index().get() as usize] = None;
         true
     }
+    /// Returns the number of components in the pool.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+    /// Returns `true` if the pool has no components.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+    /// Removes all of the components from the pool.
+    pub fn clear(&mut self) {
+        for entity in self.entities.drain(..) {
+            self.index_lookup[entity.index().get() as usize] = None;
+        }
+        self.values.clear();
+    }
     /// Returns `true` if the supplied entity has the component `T` in this pool.
     pub fn contains_entity(&self, entity: Entity) -> bool {
         self.index(entity).is_some()
@@ -125,6 +140,12 @@ impl<T> ComponentPool<T> {
     }
 }
 
+impl<T> Default for ComponentPool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Trait that erases the `T` from the component pool.
 pub trait UntypedComponentPool: Any {
     fn remove(&mut self, entity: Entity);
@@ -25,10 +25,6 @@ impl Entity {
     pub(super) fn index(self) -> NonMaxU16 {
         self.index
     }
-    /// Returns the generation of the entity.
-    pub(super) fn generation(self) -> NonMaxU16 {
-        self.generation
-    }
 }
 
 /// A registry storing the state of entities and their components.
@@ -120,6 +116,12 @@ impl World {
     }
 }
 
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Record storing the current state of the entity with some index.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
 struct EntityRecord {
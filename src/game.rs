@@ -1,8 +1,18 @@
+pub mod animation;
 pub mod bullet;
+pub mod camera;
+pub mod capture;
 pub mod collision;
+pub mod combat_text;
 pub mod enemy;
+pub mod gamepad;
 pub mod hp;
+pub mod hud;
 pub mod movement;
+pub mod particles;
 pub mod player;
 pub mod rendering;
+pub mod tilemap;
 pub mod transform;
+pub mod trigger;
+pub mod ui;
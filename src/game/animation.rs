@@ -0,0 +1,181 @@
+use std::ops::Range;
+
+use macroquad::math::{Rect, Vec2};
+use strum::{EnumCount, EnumIter};
+
+use crate::{Context, ecs::component::Component, game::rendering::Sprite};
+
+/// The kind of the animation clip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumCount, EnumIter, Hash)]
+pub enum ClipKind {
+    /// Standing still.
+    Idle,
+    /// Moving around.
+    Walk,
+    /// Taking damage.
+    Hurt,
+    /// Dying.
+    Death,
+}
+
+/// Determines what happens when the clip reaches its last frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PlaybackMode {
+    /// The clip starts over from the first frame.
+    Loop,
+    /// The clip stops on the last frame.
+    Once,
+    /// The clip switches to the clip of the specified kind after the last frame.
+    Then(ClipKind),
+}
+
+/// A sequence of frames of the [`Animation`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationClip {
+    /// The indices of the frames of the animation.
+    pub frames: Range<usize>,
+    /// How long each frame is shown in seconds.
+    pub frame_duration: f32,
+    pub mode: PlaybackMode,
+}
+
+/// A component that animates the [`Sprite`] of the entity by changing its region.
+#[derive(Debug, Clone)]
+pub struct Animation {
+    /// The regions of the texture containing the frames.
+    frames: Box<[Rect]>,
+    clips: [Option<AnimationClip>; ClipKind::COUNT],
+    kind: ClipKind,
+    /// The index of the current frame within the clip.
+    frame: usize,
+    /// The time the current frame has been shown for.
+    elapsed: f32,
+}
+
+impl Component for Animation {}
+
+impl Animation {
+    /// Constructs an animation without clips from the regions of the texture containing the frames.
+    pub fn new(frames: impl Into<Box<[Rect]>>) -> Self {
+        Self {
+            frames: frames.into(),
+            clips: Default::default(),
+            kind: ClipKind::Idle,
+            frame: 0,
+            elapsed: 0.,
+        }
+    }
+    /// Constructs an animation without clips from the texture of the specified size
+    /// split into a grid of equally sized frames. The frames are numbered left to right, top to bottom.
+    ///
+    /// # Panics
+    /// Panics if the grid is empty.
+    pub fn from_grid(texture_size: Vec2, columns: usize, rows: usize) -> Self {
+        assert!(columns != 0 && rows != 0, "Frame grid must not be empty!");
+        let (width, height) = (
+            texture_size.x / columns as f32,
+            texture_size.y / rows as f32,
+        );
+        let frames: Vec<Rect> = (0..rows)
+            .flat_map(|y| (0..columns).map(move |x| (x, y)))
+            .map(|(x, y)| Rect::new(x as f32 * width, y as f32 * height, width, height))
+            .collect();
+        Self::new(frames)
+    }
+    /// Sets the clip of the specified kind.
+    ///
+    /// # Panics
+    /// Panics if the clip has no frames or refers to the frames out of bounds.
+    pub fn set_clip(&mut self, kind: ClipKind, clip: AnimationClip) {
+        assert!(
+            !clip.frames.is_empty() && clip.frames.end <= self.frames.len(),
+            "Clip frames must be within the animation!"
+        );
+        self.clips[kind as usize] = Some(clip);
+    }
+    /// Returns the kind of the clip being played.
+    pub fn kind(&self) -> ClipKind {
+        self.kind
+    }
+    /// Switches to the clip of the specified kind from its first frame.
+    ///
+    /// Playing the looping clip that is already being played does nothing,
+    /// while the one-shot clips are always restarted.
+    pub fn play(&mut self, kind: ClipKind) {
+        let is_looping = self
+            .clip()
+            .is_some_and(|clip| clip.mode == PlaybackMode::Loop);
+        if kind == self.kind && is_looping {
+            return;
+        }
+        self.kind = kind;
+        self.frame = 0;
+        self.elapsed = 0.;
+    }
+    /// Returns `true` if the current clip is a one-shot that has reached its end.
+    pub fn is_finished(&self) -> bool {
+        self.clip().is_none_or(|clip| {
+            clip.mode == PlaybackMode::Once
+                && self.frame + 1 == clip.frames.len()
+                && self.elapsed >= clip.frame_duration
+        })
+    }
+    /// Returns `true` if the current clip can be switched without cutting a one-shot clip short.
+    pub fn is_interruptible(&self) -> bool {
+        self.clip()
+            .is_none_or(|clip| clip.mode == PlaybackMode::Loop)
+            || self.is_finished()
+    }
+    /// Advances the current clip by `delta` seconds.
+    pub fn advance(&mut self, delta: f32) {
+        let Some(clip) = self.clip() else {
+            return;
+        };
+        let (len, duration, mode) = (clip.frames.len(), clip.frame_duration, clip.mode);
+        self.elapsed += delta;
+        while self.elapsed >= duration && duration > 0. {
+            if self.frame + 1 < len {
+                self.frame += 1;
+                self.elapsed -= duration;
+                continue;
+            }
+            match mode {
+                PlaybackMode::Loop => {
+                    self.frame = 0;
+                    self.elapsed -= duration;
+                }
+                PlaybackMode::Once => {
+                    // Keep the elapsed time to report that the clip is finished.
+                    self.elapsed = duration;
+                    return;
+                }
+                PlaybackMode::Then(kind) => {
+                    self.play(kind);
+                    return;
+                }
+            }
+        }
+    }
+    /// Returns the region of the texture containing the current frame
+    /// or `None` if there is no clip of the current kind.
+    pub fn region(&self) -> Option<Rect> {
+        let clip = self.clip()?;
+        Some(self.frames[clip.frames.start + self.frame])
+    }
+    fn clip(&self) -> Option<&AnimationClip> {
+        self.clips[self.kind as usize].as_ref()
+    }
+}
+
+/// Advances the animations and updates the regions of their sprites.
+pub fn animate_sprites(context: &mut Context, delta: f32) {
+    let mut animations = context.world.borrow_pool_mut::<Animation>();
+    let mut sprites = context.world.borrow_pool_mut::<Sprite>();
+    for (entity, animation) in animations.iter_mut() {
+        animation.advance(delta);
+        sprites
+            .get_mut(entity)
+            .expect("All animations must have a Sprite component!")
+            .region = animation.region();
+    }
+}
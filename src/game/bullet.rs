@@ -1,13 +1,28 @@
+use std::f32::consts::PI;
+
 use macroquad::math::{Rect, Vec2};
 
 use crate::{
     Context, FIXED_DELTA,
     ecs::component::Component,
-    game::{collision::Collider, enemy::Enemy, hp::Hp, rendering::Sprite, transform::Transform},
+    game::{
+        animation::{Animation, ClipKind},
+        collision::Collider,
+        combat_text::CombatTextKind,
+        enemy::Enemy,
+        hp::Hp,
+        particles::ParticleEffect,
+        rendering::Sprite,
+        transform::Transform,
+    },
 };
 
 pub struct Bullet {
     pub damage: u32,
+    /// `true` if the damage is critical.
+    pub is_crit: bool,
+    /// `true` if the bullet heals the player on hit.
+    pub is_lifesteal: bool,
 }
 
 impl Component for Bullet {}
@@ -23,20 +38,33 @@ pub fn move_bullets(context: &mut Context) {
     }
 }
 pub fn test_bullet_collision(context: &mut Context) {
+    const LIFESTEAL_HEAL: u32 = 1;
     let mut to_remove = Vec::new();
+    let mut texts = Vec::new();
     {
         let enemies = context.world.borrow_pool::<Enemy>();
         let bullets = context.world.borrow_pool::<Bullet>();
         let colliders = context.world.borrow_pool::<Collider>();
         let transforms = context.world.borrow_pool::<Transform>();
-        let sprites = context.world.borrow_pool::<Sprite>();
+        let mut sprites = context.world.borrow_pool_mut::<Sprite>();
         let mut hps = context.world.borrow_pool_mut::<Hp>();
+        let mut animations = context.world.borrow_pool_mut::<Animation>();
         let c = &context.map_constraints;
         let crect = Rect::new(c.min.x, c.min.y, (c.max - c.min).x, (c.max - c.min).y);
         for (entity, bullet) in bullets.iter() {
-            let pos = transforms.get(entity).unwrap().position;
+            let Transform {
+                position: pos,
+                rotation,
+            } = *transforms.get(entity).unwrap();
             let mut is_removed = false;
-            if !crect.contains(pos) {
+            let collider = colliders.get(entity).unwrap();
+            let is_blocked = context.static_geometry.blocks(collider, pos);
+            if is_blocked {
+                context
+                    .particles
+                    .emit(&ParticleEffect::HIT, pos, rotation + PI);
+            }
+            if !crect.contains(pos) || is_blocked {
                 is_removed = true;
                 context.screen.remove_sprite(entity, &sprites);
                 to_remove.push(entity);
@@ -44,12 +72,40 @@ pub fn test_bullet_collision(context: &mut Context) {
             let Some(other_entity) = context
                 .collisions
                 .collisions(&transforms, &colliders, entity)
+                .into_iter()
                 .find(|&e| enemies.contains_entity(e))
             else {
                 continue;
             };
             let hp = hps.get_mut(other_entity).unwrap();
             hp.0 = hp.0.saturating_sub(bullet.damage);
+            let kind = if bullet.is_crit {
+                CombatTextKind::Crit
+            } else {
+                CombatTextKind::Damage
+            };
+            let other_pos = transforms.get(other_entity).unwrap().position;
+            if !is_removed {
+                context
+                    .particles
+                    .emit(&ParticleEffect::HIT, pos, rotation + PI);
+            }
+            texts.push((other_pos, kind, bullet.damage));
+            if bullet.is_lifesteal {
+                let max_hp = context.player.max_hp();
+                let player_hp = hps.get_mut(context.player.entity).unwrap();
+                let heal = LIFESTEAL_HEAL.min(max_hp.saturating_sub(player_hp.0));
+                if heal != 0 {
+                    player_hp.0 += heal;
+                    let player_pos = transforms.get(context.player.entity).unwrap().position;
+                    texts.push((player_pos, CombatTextKind::Heal, heal));
+                }
+            }
+            animations
+                .get_mut(other_entity)
+                .unwrap()
+                .play(ClipKind::Hurt);
+            sprites.get_mut(other_entity).unwrap().flash();
             if !is_removed {
                 context.screen.remove_sprite(entity, &sprites);
                 to_remove.push(entity);
@@ -57,4 +113,9 @@ pub fn test_bullet_collision(context: &mut Context) {
         }
     }
     context.world.destroy_entities(&to_remove);
+    for (pos, kind, value) in texts {
+        context
+            .combat_texts
+            .spawn(&mut context.world, pos, kind, value);
+    }
 }
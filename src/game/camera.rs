@@ -0,0 +1,204 @@
+use macroquad::math::{Rect, Vec2};
+
+/// A resource determining which part of the world is shown on the screen.
+///
+/// The camera smoothly follows its target, keeps the view within the `bounds`,
+/// shakes proportionally to the square of its trauma and can be zoomed in and out.
+/// None of its methods need a window, so the math can be checked headlessly.
+#[derive(Debug, Clone, Copy)]
+pub struct Camera {
+    /// The area the view never leaves or `None` if the view is not constrained.
+    pub bounds: Option<Rect>,
+    /// The approximate time in seconds it takes the camera to catch up with its target.
+    pub smooth_time: f32,
+    /// The size of the view in world units at zoom `1`.
+    view_size: Vec2,
+    position: Vec2,
+    velocity: Vec2,
+    zoom: f32,
+    trauma: f32,
+    /// The time used to sample the shake offset.
+    shake_time: f32,
+}
+
+impl Camera {
+    /// The smallest allowed zoom.
+    pub const ZOOM_MIN: f32 = 0.5;
+    /// The largest allowed zoom.
+    pub const ZOOM_MAX: f32 = 2.;
+    /// The largest distance the view is shaken by at full trauma.
+    const SHAKE_OFFSET_MAX: f32 = 24.;
+    /// How fast the shake oscillates.
+    const SHAKE_FREQUENCY: f32 = 25.;
+    /// How much trauma is lost every second.
+    const TRAUMA_DECAY: f32 = 1.5;
+
+    /// Constructs a camera with a view of the specified size looking at `position`.
+    pub fn new(view_size: Vec2, position: Vec2) -> Self {
+        Self {
+            bounds: None,
+            smooth_time: 0.15,
+            view_size,
+            position,
+            velocity: Vec2::ZERO,
+            zoom: 1.,
+            trauma: 0.,
+            shake_time: 0.,
+        }
+    }
+    /// Returns the zoom of the camera.
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+    /// Sets the zoom of the camera clamped between [`Self::ZOOM_MIN`] and [`Self::ZOOM_MAX`].
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom.clamp(Self::ZOOM_MIN, Self::ZOOM_MAX);
+    }
+    /// Returns the trauma of the camera between `0` and `1`.
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+    /// Adds trauma to the camera, making it shake.
+    ///
+    /// The trauma is capped at `1`.
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0., 1.);
+    }
+    /// Moves the camera towards the `target` with a critically damped spring
+    /// and lets the trauma decay over `delta` seconds.
+    pub fn update(&mut self, target: Vec2, delta: f32) {
+        // The closed-form approximation from Game Programming Gems 4, chapter 1.10.
+        let omega = 2. / self.smooth_time.max(f32::EPSILON);
+        let x = omega * delta;
+        let exp = 1. / (1. + x + 0.48 * x * x + 0.235 * x * x * x);
+        let change = self.position - target;
+        let temp = (self.velocity + omega * change) * delta;
+        self.velocity = (self.velocity - omega * temp) * exp;
+        self.position = self.clamp(target + (change + temp) * exp);
+        self.trauma = (self.trauma - Self::TRAUMA_DECAY * delta).max(0.);
+        self.shake_time += delta;
+    }
+    /// Returns the center of the view including the shake.
+    pub fn view_center(&self) -> Vec2 {
+        let shake = self.trauma * self.trauma;
+        let t = self.shake_time * Self::SHAKE_FREQUENCY;
+        // A sum of incommensurate sines looks random enough while being deterministic.
+        let noise = Vec2::new(
+            (t * 1.1).sin() * 0.6 + (t * 2.3 + 1.7).sin() * 0.4,
+            (t * 1.3 + 4.1).sin() * 0.6 + (t * 2.9 + 0.3).sin() * 0.4,
+        );
+        self.clamp(self.position + noise * Self::SHAKE_OFFSET_MAX * shake)
+    }
+    /// Sets the size of the area of the world shown on the screen at zoom `1`.
+    pub fn set_view_size(&mut self, view_size: Vec2) {
+        self.view_size = view_size;
+    }
+    /// Converts the position on the virtual screen into the position in the world.
+    pub fn screen_to_world(&self, position: Vec2) -> Vec2 {
+        self.view_rect().point() + position / self.zoom
+    }
+    /// Converts the position in the world into the position on the virtual screen.
+    pub fn world_to_screen(&self, position: Vec2) -> Vec2 {
+        (position - self.view_rect().point()) * self.zoom
+    }
+    /// Returns the area of the world shown on the screen including the shake.
+    pub fn view_rect(&self) -> Rect {
+        let size = self.view_size / self.zoom;
+        let origin = self.view_center() - size / 2.;
+        Rect::new(origin.x, origin.y, size.x, size.y)
+    }
+    /// Returns the closest view center to the supplied one that keeps the view within bounds.
+    ///
+    /// If the bounds are smaller than the view along some axis, the view is centered on them.
+    fn clamp(&self, center: Vec2) -> Vec2 {
+        let Some(bounds) = self.bounds else {
+            return center;
+        };
+        let half = self.view_size / self.zoom / 2.;
+        let min = bounds.point() + half;
+        let max = bounds.point() + bounds.size() - half;
+        let axis = |value: f32, min: f32, max: f32| {
+            if min > max {
+                (min + max) / 2.
+            } else {
+                value.clamp(min, max)
+            }
+        };
+        Vec2::new(axis(center.x, min.x, max.x), axis(center.y, min.y, max.y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIEW_SIZE: Vec2 = Vec2::new(320., 180.);
+    const DELTA: f32 = 1. / 60.;
+
+    #[test]
+    fn follow_catches_up_with_the_target_without_overshooting() {
+        let mut camera = Camera::new(VIEW_SIZE, Vec2::ZERO);
+        let target = Vec2::new(100., -50.);
+        let mut distance = camera.view_center().distance(target);
+        for _ in 0..60 {
+            camera.update(target, DELTA);
+            let new_distance = camera.view_center().distance(target);
+            assert!(new_distance < distance);
+            distance = new_distance;
+        }
+        assert!(distance < 0.01);
+    }
+
+    #[test]
+    fn view_stays_within_bounds() {
+        let mut camera = Camera::new(VIEW_SIZE, Vec2::ZERO);
+        camera.bounds = Some(Rect::new(-500., -500., 1000., 1000.));
+        for _ in 0..120 {
+            camera.update(Vec2::new(1000., -1000.), DELTA);
+        }
+        assert_eq!(camera.view_rect(), Rect::new(180., -500., 320., 180.));
+        // The bounds narrower than the view are centered on.
+        camera.bounds = Some(Rect::new(0., -500., 100., 1000.));
+        camera.update(Vec2::new(1000., -1000.), DELTA);
+        assert_eq!(camera.view_center().x, 50.);
+    }
+
+    #[test]
+    fn zoom_is_clamped_and_scales_the_view() {
+        let mut camera = Camera::new(VIEW_SIZE, Vec2::ZERO);
+        camera.set_zoom(2.);
+        assert_eq!(camera.view_rect(), Rect::new(-80., -45., 160., 90.));
+        camera.set_zoom(100.);
+        assert_eq!(camera.zoom(), Camera::ZOOM_MAX);
+        camera.set_zoom(0.);
+        assert_eq!(camera.zoom(), Camera::ZOOM_MIN);
+    }
+
+    #[test]
+    fn shake_decays_with_the_trauma() {
+        let mut camera = Camera::new(VIEW_SIZE, Vec2::ZERO);
+        camera.add_trauma(2.);
+        assert_eq!(camera.trauma(), 1.);
+        camera.update(Vec2::ZERO, 0.1);
+        assert!(camera.view_center() != Vec2::ZERO);
+        assert!((camera.trauma() - (1. - Camera::TRAUMA_DECAY * 0.1)).abs() < 1e-6);
+        for _ in 0..60 {
+            camera.update(Vec2::ZERO, DELTA);
+        }
+        assert_eq!(camera.trauma(), 0.);
+        assert_eq!(camera.view_center(), Vec2::ZERO);
+    }
+
+    #[test]
+    fn world_and_screen_positions_round_trip() {
+        let mut camera = Camera::new(VIEW_SIZE, Vec2::new(40., 30.));
+        camera.set_zoom(1.5);
+        camera.add_trauma(0.5);
+        camera.update(Vec2::new(60., 10.), DELTA);
+        assert_eq!(camera.world_to_screen(camera.view_center()), VIEW_SIZE / 2.);
+        for position in [Vec2::ZERO, Vec2::new(-123., 45.), Vec2::new(1e3, 1e3)] {
+            let screen = camera.world_to_screen(position);
+            assert!(camera.screen_to_world(screen).distance(position) < 1e-3);
+        }
+    }
+}
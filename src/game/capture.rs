@@ -0,0 +1,101 @@
+use image::{ColorType, ImageEncoder, codecs::png::PngEncoder};
+use macroquad::{miniquad::date, texture::Image};
+
+use crate::Context;
+
+#[cfg(target_arch = "wasm32")]
+unsafe extern "C" {
+    /// Makes the browser download the file (see `tools/build-wasm/templates/capture.js`).
+    fn last_stand_download(name: *const u8, name_len: usize, data: *const u8, data_len: usize);
+}
+
+/// A resource dumping the frames of the virtual screen into numbered PNGs.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameCapture {
+    /// Only every `every`-th frame is dumped.
+    pub every: u32,
+    is_recording: bool,
+    frame: u32,
+    /// The number of the next dumped frame.
+    index: u32,
+}
+
+impl FrameCapture {
+    /// Constructs a capture dumping every `every`-th frame once started.
+    pub fn new(every: u32) -> Self {
+        assert!(every > 0, "Frame capture must dump at least some frames!");
+        Self {
+            every,
+            is_recording: false,
+            frame: 0,
+            index: 0,
+        }
+    }
+    pub fn is_recording(&self) -> bool {
+        self.is_recording
+    }
+    /// Starts or stops dumping the frames. The numbering continues from the previous recording.
+    pub fn toggle_recording(&mut self) {
+        self.is_recording = !self.is_recording;
+        self.frame = 0;
+    }
+}
+
+/// Encodes the image read back from a render target as a PNG.
+///
+/// The render targets are stored upside down, so the rows are flipped.
+pub fn encode_png(image: &Image) -> Vec<u8> {
+    let row_len = image.width as usize * 4;
+    let flipped: Vec<u8> = image
+        .bytes
+        .chunks_exact(row_len)
+        .rev()
+        .flatten()
+        .copied()
+        .collect();
+    let mut res = Vec::new();
+    PngEncoder::new(&mut res)
+        .write_image(
+            &flipped,
+            image.width as u32,
+            image.height as u32,
+            ColorType::Rgba8,
+        )
+        .expect("Image must fit into a PNG!");
+    res
+}
+
+/// Writes the PNG into a file on native platforms and downloads it in the browser.
+pub fn save_png(name: &str, png: &[u8]) {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Err(error) = std::fs::write(name, png) {
+        eprintln!("Failed to save {name}: {error}");
+    }
+    #[cfg(target_arch = "wasm32")]
+    unsafe {
+        last_stand_download(name.as_ptr(), name.len(), png.as_ptr(), png.len());
+    }
+}
+
+/// Saves the last frame of the virtual screen as `screenshot-<unix time>.png`.
+pub fn take_screenshot(context: &Context) {
+    let png = encode_png(&context.screen.read_pixels());
+    save_png(&format!("screenshot-{}.png", date::now() as u64), &png);
+}
+
+/// Dumps the last frame of the virtual screen as `frame-<number>.png`
+/// if the [`FrameCapture`] is recording and it's the frame's turn.
+pub fn capture_frame(context: &mut Context) {
+    let capture = &mut context.capture;
+    if !capture.is_recording {
+        return;
+    }
+    capture.frame += 1;
+    if capture.frame < capture.every {
+        return;
+    }
+    capture.frame = 0;
+    let png = encode_png(&context.screen.read_pixels());
+    save_png(&format!("frame-{:05}.png", capture.index), &png);
+    capture.index += 1;
+}
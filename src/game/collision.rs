@@ -1,26 +1,29 @@
-use std::ops::Range;
-
 use macroquad::math::{Circle, Rect, Vec2};
+use strum::{Display, EnumIter, EnumString};
 
 use crate::{
     ecs::{
         component::{Component, ComponentPool},
         entity::Entity,
     },
-    game::transform::Transform,
+    game::{
+        collision::{
+            grid::CollisionGrid,
+            hash::SpatialHash,
+            layer::{COLLISION_MATRIX, CollisionMask},
+            quadtree::LooseQuadtree,
+            sweep::SortAndSweep,
+        },
+        transform::Transform,
+    },
 };
 
-/// A mask determining the layer within collision system.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
-pub struct CollisionMask(pub u8);
-
-impl CollisionMask {
-    /// Returns `true` if the two collision masks have
-    /// overlapping layers.
-    pub fn overlaps(self, other: Self) -> bool {
-        (self.0 & other.0) != 0
-    }
-}
+pub mod grid;
+pub mod hash;
+pub mod layer;
+pub mod quadtree;
+pub mod statics;
+pub mod sweep;
 
 #[derive(Debug, Clone, Copy)]
 pub enum Shape {
@@ -36,184 +39,687 @@ pub struct Collider {
     pub monitorable: CollisionMask,
     /// The layers that the collider will scan to detect collisions.
     pub monitoring: CollisionMask,
+    /// Physical body of the collider or `None` if the collider never pushes or gets pushed.
+    pub body: Option<Body>,
 }
 
 impl Component for Collider {}
 
-/// Determines the shape and precision of the [`CollisionGrid`].
-#[derive(Debug, Clone, Copy, Default)]
-pub struct CollisionGridParams {
-    pub bounding_rect: Rect,
-    pub resolution: (usize, usize),
+impl Collider {
+    /// Returns the bounding box of the collider at the supplied position.
+    pub fn bounds(&self, position: Vec2) -> Rect {
+        let Shape::Circle { radius } = self.shape;
+        Rect::new(
+            position.x - radius,
+            position.y - radius,
+            radius * 2.,
+            radius * 2.,
+        )
+    }
 }
 
-/// Data structure that allows to efficiently detect collisions
-/// for the [`Collider`] components.
-#[derive(Debug, Clone, Default)]
-pub struct CollisionGrid {
-    params: CollisionGridParams,
-    entities: Vec<Entity>,
-    cell_ranges: Vec<Range<u16>>,
+/// Determines how the collider is pushed apart from other colliders when they overlap.
+#[derive(Debug, Clone, Copy)]
+pub struct Body {
+    /// The layers of the colliders this body can not overlap with.
+    pub blocked_by: CollisionMask,
+    /// The mass of the body. When two bodies block each other,
+    /// the lighter body gets pushed further.
+    pub mass: f32,
+    /// If `true` the body pushes other bodies, but is never pushed itself.
+    pub is_immovable: bool,
 }
 
-impl CollisionGrid {
-    /// Constructs and empty collision grid.
-    pub fn new() -> Self {
-        Default::default()
-    }
-    /// Returns the params from the last time the grid was updated.
-    pub fn params(&self) -> &CollisionGridParams {
-        &self.params
+impl Body {
+    /// Returns `true` if this body should be pushed out of the other collider.
+    fn is_blocked_by(&self, collider: &Collider, other: &Collider) -> bool {
+        !self.is_immovable
+            && self.blocked_by.overlaps(other.monitorable)
+            && COLLISION_MATRIX.allows(collider.monitorable, other.monitorable)
     }
-    /// Returns the entities that might have collisions in the cell \[`x`, `y`\].
+}
+
+/// A broadphase data structure that allows to efficiently find
+/// the [`Collider`] components that might overlap.
+///
+/// The implementations only track the bounding boxes of the colliders from the last update,
+/// while the provided methods check the actual shapes and collision masks.
+pub trait SpatialIndex {
+    /// Updates the index to match the current positions of the colliders.
     ///
-    /// # Panics
-    /// Panics if the `x` or `y` exceeds the current resolution.
-    pub fn cell(&self, x: usize, y: usize) -> &[Entity] {
-        let range = self.cell_ranges[self.cell_index(x, y)].clone();
-        let range = Range {
-            start: range.start as usize,
-            end: range.end as usize,
-        };
-        &self.entities[range]
-    }
-    /// Rebuilds the collision grid with the new parameters.
-    pub fn update(
+    /// The `bounding_rect` is the area where most of the colliders are expected to be.
+    fn update(
         &mut self,
         transforms: &ComponentPool<Transform>,
         colliders: &ComponentPool<Collider>,
-        params: CollisionGridParams,
-    ) {
-        self.params = params;
-        let cell_count = params.resolution.0.strict_mul(params.resolution.1);
-
-        // Pass 1: count cells.
-        self.cell_ranges.clear();
-        self.cell_ranges.resize(cell_count, Range::default());
-        for (entity, &collider) in colliders.iter() {
-            let transform = transforms
-                .get(entity)
-                .expect("All colliders must have a Transform component!");
-            for (x, y) in self.overlapping_cells(transform.position, collider.shape) {
-                let index = self.cell_index(x, y);
-                self.cell_ranges[index].end += 1;
-            }
-        }
-        // `self.cell_ranges[i]` is `0..count`, where `count` is number of entities in the cell
-
-        // Build ranges.
-        let mut total = 0;
-        for y in 0..params.resolution.0 {
-            for x in 0..params.resolution.1 {
-                let index = self.cell_index(x, y);
-                let range = &mut self.cell_ranges[index];
-                let count = range.end as usize;
-                range.start = total as u16;
-                range.end = total as u16;
-                total += count;
-            }
-        }
-        // `self.cell_ranges[i]` is `index..index` where `index` is the future begining of the range.
-        // `total` is the total number of entities.
-
-        // Pass 2: fill entities.
-        self.entities.clear();
-        self.entities.reserve(total);
-        for (entity, &collider) in colliders.iter() {
-            let transform = transforms
-                .get(entity)
-                .expect("All colliders must have a Transform component!");
-            for (x, y) in self.overlapping_cells(transform.position, collider.shape) {
-                let index = self.cell_index(x, y);
-                let range = &mut self.cell_ranges[index];
-                self.entities.spare_capacity_mut()[range.end as usize].write(entity);
-                range.end += 1;
-            }
-        }
-        // `self.cell_ranges[i]` is `start..end` where `start` and `end` indicate the positions
-        // of the corresponding entities within the spare capacity of `self.entities`.
+        bounding_rect: Rect,
+    );
+    /// Appends the entities whose bounding boxes might overlap with the `area` to `out`.
+    ///
+    /// Each entity is appended at most once.
+    fn query_area(&self, area: Rect, out: &mut Vec<Entity>);
+    /// Appends the pairs of entities whose bounding boxes might overlap to `out`.
+    ///
+    /// Each pair is appended at most once.
+    fn candidate_pairs(&self, out: &mut Vec<(Entity, Entity)>);
 
-        // SAFETY: This is safe since `total` is the total number of entities
-        // and we filled in the entities into the spare capacity in "pass 2".
-        unsafe {
-            self.entities.set_len(total);
-        }
+    /// Appends the cells of the index alongside the number of entries in each of them to `out`.
+    ///
+    /// Only used for debugging, so by default nothing is appended.
+    fn debug_cells(&self, out: &mut Vec<(Rect, usize)>) {
+        let _ = out;
     }
-    pub fn collisions(
+
+    /// Returns the entities whose colliders collide with the collider of the supplied entity.
+    ///
+    /// The collisions between the layers not allowed by the [`COLLISION_MATRIX`] are ignored.
+    ///
+    /// # Panics
+    /// Panics if the entity does not have a collider.
+    fn collisions(
         &self,
         transforms: &ComponentPool<Transform>,
         colliders: &ComponentPool<Collider>,
         entity: Entity,
-    ) -> impl Iterator<Item = Entity> {
+    ) -> Vec<Entity> {
         let collider = colliders
             .get(entity)
             .copied()
             .expect("Entity must have a collider!");
-        let transform = transforms.get(entity).copied().unwrap();
-        let circle = match collider.shape {
-            Shape::Circle { radius } => Circle {
-                x: transform.position.x,
-                y: transform.position.y,
-                r: radius,
-            },
-        };
-        self.overlapping_cells(transform.position, collider.shape)
-            .flat_map(|(x, y)| self.cell(x, y).iter().copied())
-            .filter(move |entity| {
-                let other_collider = colliders.get(*entity).unwrap();
-                let other_transform = transforms.get(*entity).unwrap();
-                if !collider.monitoring.overlaps(other_collider.monitorable)
-                    && !other_collider.monitoring.overlaps(collider.monitorable)
-                {
-                    return false;
-                }
-                let other_circle = match collider.shape {
-                    Shape::Circle { radius } => Circle {
-                        x: other_transform.position.x,
-                        y: other_transform.position.y,
-                        r: radius,
-                    },
-                };
-                if !other_circle.overlaps(&circle.clone()) {
-                    return false;
-                }
-                true
+        let position = transforms.get(entity).unwrap().position;
+        let circle = collider_circle(position, collider.shape);
+        let mut res = Vec::new();
+        self.query_area(collider.bounds(position), &mut res);
+        res.retain(|&other_entity| {
+            let Some(other_collider) = colliders.get(other_entity) else {
+                return false;
+            };
+            if other_entity == entity
+                || (!collider.monitoring.overlaps(other_collider.monitorable)
+                    && !other_collider.monitoring.overlaps(collider.monitorable))
+                || !COLLISION_MATRIX.allows(collider.monitorable, other_collider.monitorable)
+            {
+                return false;
+            }
+            let other_position = transforms.get(other_entity).unwrap().position;
+            collider_circle(other_position, other_collider.shape).overlaps(&circle)
+        });
+        res
+    }
+    /// Returns the entities whose colliders are detectable within `mask`
+    /// and overlap with the circle.
+    fn query_circle(
+        &self,
+        transforms: &ComponentPool<Transform>,
+        colliders: &ComponentPool<Collider>,
+        center: Vec2,
+        radius: f32,
+        mask: CollisionMask,
+    ) -> Vec<Entity> {
+        let circle = Circle::new(center.x, center.y, radius);
+        let area = Rect::new(
+            center.x - radius,
+            center.y - radius,
+            radius * 2.,
+            radius * 2.,
+        );
+        let mut res = Vec::new();
+        self.query_area(area, &mut res);
+        res.retain(|&entity| {
+            colliders.get(entity).is_some_and(|collider| {
+                let position = transforms.get(entity).unwrap().position;
+                collider.monitorable.overlaps(mask)
+                    && collider_circle(position, collider.shape).overlaps(&circle)
+            })
+        });
+        res
+    }
+    /// Returns the entities whose colliders are detectable within `mask`
+    /// and overlap with the rectangle.
+    fn query_rect(
+        &self,
+        transforms: &ComponentPool<Transform>,
+        colliders: &ComponentPool<Collider>,
+        rect: Rect,
+        mask: CollisionMask,
+    ) -> Vec<Entity> {
+        let mut res = Vec::new();
+        self.query_area(rect, &mut res);
+        res.retain(|&entity| {
+            colliders.get(entity).is_some_and(|collider| {
+                let position = transforms.get(entity).unwrap().position;
+                collider.monitorable.overlaps(mask)
+                    && collider_circle(position, collider.shape).overlaps_rect(&rect)
             })
+        });
+        res
     }
-    /// Returns the index in the cell array for the specified point.
+    /// Returns up to `k` entities detectable within `mask` whose positions
+    /// are no further than `max_dist` from `position`, closest first.
     ///
-    /// # Panics
-    /// Panics if the point is out of bounds.
-    fn cell_index(&self, x: usize, y: usize) -> usize {
-        let res = self.params.resolution;
-        assert!(x < res.0 && y < res.1, "The cell index is out of bounds!");
-        y * res.0 + x
-    }
-    /// Returns an iterator of all cell indices that overlap with the provided collider.
-    fn overlapping_cells(
+    /// By default the search area starts small and is doubled until
+    /// enough entities are found or `max_dist` is reached. This only needs
+    /// [`SpatialIndex::query_area`], but queries the inner area again every time it grows,
+    /// so the indices that can walk outward from the `position` should override it.
+    fn nearest(
         &self,
+        transforms: &ComponentPool<Transform>,
+        colliders: &ComponentPool<Collider>,
         position: Vec2,
-        shape: Shape,
-    ) -> impl Iterator<Item = (usize, usize)> + 'static {
-        let Shape::Circle { radius } = shape;
-        let min = position - Vec2::splat(radius);
-        let max = position + Vec2::splat(radius);
-        let res = Vec2::new(
-            self.params.resolution.0 as f32,
-            self.params.resolution.1 as f32,
+        mask: CollisionMask,
+        max_dist: f32,
+        k: usize,
+    ) -> Vec<Entity> {
+        const SEARCH_DIST_MIN: f32 = 64.;
+        if k == 0 {
+            return Vec::new();
+        }
+        let mut candidates = Vec::new();
+        let mut dist = SEARCH_DIST_MIN.min(max_dist);
+        loop {
+            let area = Rect::new(position.x - dist, position.y - dist, dist * 2., dist * 2.);
+            candidates.clear();
+            self.query_area(area, &mut candidates);
+            let mut found: Vec<(f32, Entity)> = candidates
+                .iter()
+                .filter_map(|&entity| {
+                    let collider = colliders.get(entity)?;
+                    let other_dist = transforms.get(entity).unwrap().position.distance(position);
+                    (collider.monitorable.overlaps(mask) && other_dist <= dist)
+                        .then_some((other_dist, entity))
+                })
+                .collect();
+            if found.len() >= k || dist >= max_dist {
+                found.sort_by(|a, b| a.0.total_cmp(&b.0));
+                return found
+                    .into_iter()
+                    .take(k)
+                    .map(|(_, entity)| entity)
+                    .collect();
+            }
+            dist = (dist * 2.).min(max_dist);
+        }
+    }
+    /// Pushes apart the overlapping colliders whose bodies block each other.
+    ///
+    /// The candidate pairs are gathered once from the index into the `contacts` scratch buffer,
+    /// after which the overlaps are resolved for the specified number of `iterations`.
+    /// More iterations make the crowds settle faster at a higher cost.
+    ///
+    /// Every pushed collider is moved to the position returned by `constrain`
+    /// (e.g. out of the walls and back onto the map), so that the crowds can't push
+    /// the bodies where they couldn't walk. The index isn't updated, so it has to be
+    /// updated again before it's queried for the new positions.
+    fn resolve_overlaps(
+        &self,
+        transforms: &mut ComponentPool<Transform>,
+        colliders: &ComponentPool<Collider>,
+        iterations: usize,
+        contacts: &mut Vec<(Entity, Entity)>,
+        constrain: &dyn Fn(&Collider, Vec2) -> Vec2,
+    ) {
+        contacts.clear();
+        self.candidate_pairs(contacts);
+        contacts.retain(|&(entity, other_entity)| {
+            let (Some(collider), Some(other_collider)) =
+                (colliders.get(entity), colliders.get(other_entity))
+            else {
+                return false;
+            };
+            let (Some(body), Some(other_body)) = (collider.body, other_collider.body) else {
+                return false;
+            };
+            body.is_blocked_by(collider, other_collider)
+                || other_body.is_blocked_by(other_collider, collider)
+        });
+        for _ in 0..iterations {
+            for &(entity, other_entity) in contacts.iter() {
+                let collider = colliders.get(entity).unwrap();
+                let other_collider = colliders.get(other_entity).unwrap();
+                let position = transforms.get(entity).unwrap().position;
+                let other_position = transforms.get(other_entity).unwrap().position;
+                let Some(push) = separation(position, collider, other_position, other_collider)
+                else {
+                    continue;
+                };
+                transforms.get_mut(entity).unwrap().position =
+                    constrain(collider, position - push.0);
+                transforms.get_mut(other_entity).unwrap().position =
+                    constrain(other_collider, other_position + push.1);
+            }
+        }
+    }
+}
+
+/// The broadphase used to detect the collisions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash, Display, EnumString, EnumIter)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Broadphase {
+    /// A uniform grid (see [`CollisionGrid`]).
+    #[default]
+    Grid,
+    /// A loose quadtree (see [`LooseQuadtree`]).
+    LooseQuadtree,
+    /// A sort-and-sweep along the horizontal axis (see [`SortAndSweep`]).
+    SortAndSweep,
+    /// An unbounded hashed grid (see [`SpatialHash`]).
+    SpatialHash,
+}
+
+impl Broadphase {
+    /// Constructs an empty spatial index of this kind.
+    pub fn build(self) -> Box<dyn SpatialIndex> {
+        match self {
+            Self::Grid => Box::new(CollisionGrid::new()),
+            Self::LooseQuadtree => Box::new(LooseQuadtree::new()),
+            Self::SortAndSweep => Box::new(SortAndSweep::new()),
+            Self::SpatialHash => Box::new(SpatialHash::new()),
+        }
+    }
+}
+
+/// Returns how far each of the two overlapping colliders has to be moved
+/// (the first one backwards and the second one forwards) to separate them
+/// or `None` if they don't overlap or don't block each other.
+fn separation(
+    position: Vec2,
+    collider: &Collider,
+    other_position: Vec2,
+    other_collider: &Collider,
+) -> Option<(Vec2, Vec2)> {
+    let (body, other_body) = (collider.body?, other_collider.body?);
+    let (
+        Shape::Circle { radius },
+        Shape::Circle {
+            radius: other_radius,
+        },
+    ) = (collider.shape, other_collider.shape);
+    let delta = other_position - position;
+    let dist = delta.length();
+    let overlap = radius + other_radius - dist;
+    if overlap <= 0. {
+        return None;
+    }
+    let normal = if dist > f32::EPSILON {
+        delta / dist
+    } else {
+        Vec2::X
+    };
+    let (weight, other_weight) = match (
+        body.is_blocked_by(collider, other_collider),
+        other_body.is_blocked_by(other_collider, collider),
+    ) {
+        (false, false) => return None,
+        (true, false) => (1., 0.),
+        (false, true) => (0., 1.),
+        (true, true) => {
+            let total = body.mass + other_body.mass;
+            if total > 0. {
+                (other_body.mass / total, body.mass / total)
+            } else {
+                (0.5, 0.5)
+            }
+        }
+    };
+    Some((normal * overlap * weight, normal * overlap * other_weight))
+}
+
+/// Returns the bounding circle of the collider.
+fn collider_circle(position: Vec2, shape: Shape) -> Circle {
+    let Shape::Circle { radius } = shape;
+    Circle::new(position.x, position.y, radius)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use macroquad::rand::{gen_range, srand};
+    use strum::IntoEnumIterator;
+
+    use super::*;
+    use crate::{
+        ecs::entity::World,
+        game::collision::{
+            layer::CollisionLayer,
+            statics::{StaticGeometry, StaticShape},
+        },
+    };
+
+    const AREA: Rect = Rect::new(-1000., -1000., 2000., 2000.);
+
+    /// Spawns colliders of random sizes scattered over the [`AREA`],
+    /// alternating between the enemy and the pickup layers.
+    fn scattered_world(count: usize) -> World {
+        srand(count as u64);
+        let mut world = World::new();
+        world.register_type::<Transform>();
+        world.register_type::<Collider>();
+        for index in 0..count {
+            let entity = world.create_entity();
+            let layer = if index % 2 == 0 {
+                CollisionLayer::Enemy
+            } else {
+                CollisionLayer::Pickup
+            };
+            world.borrow_pool_mut::<Transform>().insert(
+                entity,
+                Transform {
+                    position: Vec2::new(
+                        gen_range(AREA.left(), AREA.right()),
+                        gen_range(AREA.top(), AREA.bottom()),
+                    ),
+                    rotation: 0.,
+                },
+            );
+            world.borrow_pool_mut::<Collider>().insert(
+                entity,
+                Collider {
+                    shape: Shape::Circle {
+                        radius: gen_range(5., 60.),
+                    },
+                    monitorable: layer.mask(),
+                    monitoring: CollisionMask::NONE,
+                    body: None,
+                },
+            );
+        }
+        world
+    }
+
+    fn build_index(broadphase: Broadphase, world: &World) -> Box<dyn SpatialIndex> {
+        let mut res = broadphase.build();
+        res.update(
+            &world.borrow_pool::<Transform>(),
+            &world.borrow_pool::<Collider>(),
+            AREA,
+        );
+        res
+    }
+
+    /// Returns the entities whose bounding boxes overlap the `area`.
+    fn brute_force_area(world: &World, area: Rect) -> HashSet<Entity> {
+        let transforms = world.borrow_pool::<Transform>();
+        let colliders = world.borrow_pool::<Collider>();
+        colliders
+            .iter()
+            .filter(|(entity, collider)| {
+                let position = transforms.get(*entity).unwrap().position;
+                collider.bounds(position).overlaps(&area)
+            })
+            .map(|(entity, _)| entity)
+            .collect()
+    }
+
+    #[test]
+    fn candidate_pairs_contain_every_overlap_once() {
+        let world = scattered_world(600);
+        let transforms = world.borrow_pool::<Transform>();
+        let colliders = world.borrow_pool::<Collider>();
+        let entries: Vec<_> = colliders
+            .iter()
+            .map(|(entity, collider)| {
+                let position = transforms.get(entity).unwrap().position;
+                (entity, collider.bounds(position))
+            })
+            .collect();
+        let mut overlaps = Vec::new();
+        for (i, &(entity, bounds)) in entries.iter().enumerate() {
+            for &(other_entity, other_bounds) in &entries[i + 1..] {
+                if bounds.overlaps(&other_bounds) {
+                    overlaps.push((entity, other_entity));
+                }
+            }
+        }
+        for broadphase in Broadphase::iter() {
+            let index = build_index(broadphase, &world);
+            let mut pairs = Vec::new();
+            index.candidate_pairs(&mut pairs);
+            let mut found = HashSet::new();
+            for &(entity, other_entity) in &pairs {
+                assert!(
+                    found.insert((entity, other_entity)) && found.insert((other_entity, entity)),
+                    "{broadphase} reported a pair twice!"
+                );
+            }
+            for pair in &overlaps {
+                assert!(found.contains(pair), "{broadphase} missed an overlap!");
+            }
+        }
+    }
+
+    #[test]
+    fn query_area_finds_overlapping_bounds_once() {
+        let world = scattered_world(400);
+        let areas = [
+            Rect::new(-100., -100., 200., 200.),
+            Rect::new(-1100., 500., 300., 800.),
+            Rect::new(-5000., -5000., 10000., 10000.),
+            Rect::new(-1e30, -1e30, 2e30, 2e30),
+        ];
+        for broadphase in Broadphase::iter() {
+            let index = build_index(broadphase, &world);
+            for area in areas {
+                let mut found = Vec::new();
+                index.query_area(area, &mut found);
+                let unique: HashSet<_> = found.iter().copied().collect();
+                assert_eq!(
+                    unique.len(),
+                    found.len(),
+                    "{broadphase} reported duplicates!"
+                );
+                assert!(
+                    unique.is_superset(&brute_force_area(&world, area)),
+                    "{broadphase} missed an entity!"
+                );
+            }
+        }
+    }
+
+    /// Returns the entities detectable within the `mask` for which `overlaps` returns `true`.
+    fn brute_force_shapes(
+        world: &World,
+        mask: CollisionMask,
+        overlaps: impl Fn(Circle) -> bool,
+    ) -> HashSet<Entity> {
+        let transforms = world.borrow_pool::<Transform>();
+        let colliders = world.borrow_pool::<Collider>();
+        colliders
+            .iter()
+            .filter(|(entity, collider)| {
+                let position = transforms.get(*entity).unwrap().position;
+                collider.monitorable.overlaps(mask)
+                    && overlaps(collider_circle(position, collider.shape))
+            })
+            .map(|(entity, _)| entity)
+            .collect()
+    }
+
+    #[test]
+    fn grid_queries_match_brute_force() {
+        let world = scattered_world(800);
+        let index = build_index(Broadphase::Grid, &world);
+        let transforms = world.borrow_pool::<Transform>();
+        let colliders = world.borrow_pool::<Collider>();
+        let mask = CollisionLayer::Enemy.mask();
+        for (center, radius) in [
+            (Vec2::ZERO, 150.),
+            (Vec2::new(-990., 990.), 300.),
+            (Vec2::new(400., -250.), 1.),
+            (Vec2::new(3000., 0.), 100.),
+        ] {
+            let found = index.query_circle(&transforms, &colliders, center, radius, mask);
+            let circle = Circle::new(center.x, center.y, radius);
+            let expected = brute_force_shapes(&world, mask, |other| other.overlaps(&circle));
+            assert_eq!(
+                found.len(),
+                expected.len(),
+                "Circle query returned duplicates!"
+            );
+            assert_eq!(found.into_iter().collect::<HashSet<_>>(), expected);
+        }
+        for rect in [
+            Rect::new(-200., -100., 400., 200.),
+            Rect::new(-1200., -1200., 500., 2400.),
+            Rect::new(10., 10., 1., 1.),
+            AREA,
+        ] {
+            let found = index.query_rect(&transforms, &colliders, rect, mask);
+            let expected = brute_force_shapes(&world, mask, |other| other.overlaps_rect(&rect));
+            assert_eq!(
+                found.len(),
+                expected.len(),
+                "Rect query returned duplicates!"
+            );
+            assert_eq!(found.into_iter().collect::<HashSet<_>>(), expected);
+        }
+    }
+
+    #[test]
+    fn nearest_matches_brute_force() {
+        let world = scattered_world(500);
+        let transforms = world.borrow_pool::<Transform>();
+        let colliders = world.borrow_pool::<Collider>();
+        let mask = CollisionLayer::Enemy.mask();
+        for broadphase in Broadphase::iter() {
+            let index = build_index(broadphase, &world);
+            for (position, max_dist, k) in [
+                (Vec2::ZERO, 300., 5),
+                (Vec2::new(900., -950.), 2000., 3),
+                (Vec2::new(-1500., 0.), 600., 10),
+                (Vec2::new(200., 200.), 10., 4),
+            ] {
+                let mut expected: Vec<(f32, Entity)> = colliders
+                    .iter()
+                    .filter(|(_, collider)| collider.monitorable.overlaps(mask))
+                    .map(|(entity, _)| {
+                        let dist = transforms.get(entity).unwrap().position.distance(position);
+                        (dist, entity)
+                    })
+                    .filter(|&(dist, _)| dist <= max_dist)
+                    .collect();
+                expected.sort_by(|a, b| a.0.total_cmp(&b.0));
+                let expected: Vec<_> = expected.into_iter().take(k).map(|(_, e)| e).collect();
+                let found = index.nearest(&transforms, &colliders, position, mask, max_dist, k);
+                assert_eq!(found, expected, "{broadphase} found other entities!");
+            }
+        }
+    }
+
+    #[test]
+    fn grid_updates_match_rebuilds() {
+        let mut world = scattered_world(400);
+        let mut grid = CollisionGrid::new();
+        let mut rebuilt = CollisionGrid::new();
+        // Nudge every collider a little, then teleport most of them,
+        // so that both the incremental updates and the rebuilds are taken.
+        for tick in 0..6 {
+            {
+                let mut transforms = world.borrow_pool_mut::<Transform>();
+                for (index, (_, transform)) in transforms.iter_mut().enumerate() {
+                    if tick == 3 && index % 4 != 0 {
+                        transform.position = -transform.position;
+                    } else {
+                        transform.position.x += 3.;
+                    }
+                }
+            }
+            if tick == 4 {
+                let removed: Vec<_> = world
+                    .borrow_pool::<Collider>()
+                    .entities()
+                    .take(50)
+                    .collect();
+                world.destroy_entities(&removed);
+            }
+            let transforms = world.borrow_pool::<Transform>();
+            let colliders = world.borrow_pool::<Collider>();
+            grid.update(&transforms, &colliders, AREA);
+            rebuilt.rebuild(&transforms, &colliders, *grid.params());
+            assert_eq!(grid.entry_count(), rebuilt.entry_count());
+            let (width, height) = grid.params().resolution;
+            for (x, y) in (0..height).flat_map(|y| (0..width).map(move |x| (x, y))) {
+                let cell: HashSet<_> = grid.cell(x, y).iter().copied().collect();
+                let other: HashSet<_> = rebuilt.cell(x, y).iter().copied().collect();
+                assert!(cell == other, "Updated grid diverged from the rebuilt one!");
+            }
+        }
+    }
+
+    const BODY: Collider = Collider {
+        shape: Shape::Circle { radius: 10. },
+        monitorable: CollisionLayer::Enemy.mask(),
+        monitoring: CollisionMask::NONE,
+        body: Some(Body {
+            blocked_by: CollisionLayer::Enemy.mask(),
+            mass: 1.,
+            is_immovable: false,
+        }),
+    };
+
+    /// Spawns the [`BODY`] colliders on the horizontal axis.
+    fn bodies<const N: usize>(xs: [f32; N]) -> (World, [Entity; N]) {
+        let mut world = World::new();
+        world.register_type::<Transform>();
+        world.register_type::<Collider>();
+        let entities = xs.map(|x| {
+            let entity = world.create_entity();
+            world.borrow_pool_mut::<Transform>().insert(
+                entity,
+                Transform {
+                    position: Vec2::new(x, 0.),
+                    rotation: 0.,
+                },
+            );
+            world.borrow_pool_mut::<Collider>().insert(entity, BODY);
+            entity
+        });
+        (world, entities)
+    }
+
+    #[test]
+    fn resolve_overlaps_pushes_bodies_apart() {
+        let (world, entities) = bodies([0., 5.]);
+        let index = build_index(Broadphase::Grid, &world);
+        let mut contacts = vec![(entities[0], entities[0]); 16];
+        index.resolve_overlaps(
+            &mut world.borrow_pool_mut::<Transform>(),
+            &world.borrow_pool::<Collider>(),
+            1,
+            &mut contacts,
+            &|_, position| position,
+        );
+        assert_eq!(contacts.len(), 1);
+        assert!(contacts.capacity() >= 16);
+        let transforms = world.borrow_pool::<Transform>();
+        let [a, b] = entities.map(|entity| transforms.get(entity).unwrap().position);
+        assert!((a.distance(b) - 20.).abs() < 1e-4);
+        assert!(
+            ((a + b) / 2.).distance(Vec2::new(2.5, 0.)) < 1e-4,
+            "Equal masses must be pushed equally!"
+        );
+    }
+
+    #[test]
+    fn resolve_overlaps_keeps_bodies_out_of_walls() {
+        let wall = Rect::new(20., -50., 50., 100.);
+        let geometry = StaticGeometry::new(vec![StaticShape::Rect(wall)], 100.);
+        let (world, entities) = bodies([-10., 0., 8.]);
+        let index = build_index(Broadphase::Grid, &world);
+        index.resolve_overlaps(
+            &mut world.borrow_pool_mut::<Transform>(),
+            &world.borrow_pool::<Collider>(),
+            4,
+            &mut Vec::new(),
+            &|collider, position| geometry.push_out(collider, position),
+        );
+        let transforms = world.borrow_pool::<Transform>();
+        let [a, b, c] = entities.map(|entity| transforms.get(entity).unwrap().position);
+        assert!(
+            (c.x - (wall.left() - 10.)).abs() < 1e-4,
+            "Body must be pushed against the wall!"
         );
-        let unit = self.params.bounding_rect.size() / res;
-        let origin = self.params.bounding_rect.point();
-        let local_min = ((min - origin) / unit).floor().max(Vec2::ZERO);
-        let local_max = ((max - origin) / unit).ceil().min(res);
-        let range_x = Range {
-            start: local_min.x as usize,
-            end: local_max.x as usize,
-        };
-        let range_y = Range {
-            start: local_min.y as usize,
-            end: local_max.y as usize,
-        };
-        range_y.flat_map(move |y| range_x.clone().map(move |x| (x, y)))
+        assert!(!geometry.blocks(&BODY, c));
+        assert!(a.x < b.x && b.x < c.x);
     }
 }
@@ -0,0 +1,537 @@
+use std::ops::Range;
+
+use macroquad::math::{Rect, Vec2};
+
+use crate::{
+    ecs::{component::ComponentPool, entity::Entity},
+    game::{
+        collision::{Collider, CollisionMask, Shape, SpatialIndex},
+        transform::Transform,
+    },
+};
+
+/// Determines the shape and precision of the [`CollisionGrid`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CollisionGridParams {
+    pub bounding_rect: Rect,
+    pub resolution: (usize, usize),
+}
+
+impl CollisionGridParams {
+    /// The number of cell entries the automatic resolution aims for in each cell.
+    const ENTRIES_PER_CELL: f32 = 4.;
+    /// The largest resolution along either axis the automatic resolution can pick.
+    const RESOLUTION_MAX: usize = 256;
+
+    /// Constructs the params with the resolution picked from
+    /// the number and the average size of the colliders.
+    ///
+    /// The cells are made large enough for the average collider to
+    /// only overlap a couple of them, but small enough to hold
+    /// [`Self::ENTRIES_PER_CELL`] colliders on average.
+    pub fn auto(bounding_rect: Rect, colliders: &ComponentPool<Collider>) -> Self {
+        let count = colliders.len();
+        let size = bounding_rect.size();
+        if count == 0 || size.x <= 0. || size.y <= 0. {
+            return Self {
+                bounding_rect,
+                resolution: (1, 1),
+            };
+        }
+        let radius_total: f32 = colliders
+            .values()
+            .map(|collider| {
+                let Shape::Circle { radius } = collider.shape;
+                radius
+            })
+            .sum();
+        let radius_avg = radius_total / count as f32;
+        let area_per_cell = size.x * size.y * Self::ENTRIES_PER_CELL / count as f32;
+        let cell_side = f32::max(radius_avg * 4., area_per_cell.sqrt()).max(1.);
+        let resolution =
+            |side: f32| ((side / cell_side).ceil() as usize).clamp(1, Self::RESOLUTION_MAX);
+        Self {
+            bounding_rect,
+            resolution: (resolution(size.x), resolution(size.y)),
+        }
+    }
+}
+
+/// The cells overlapped by the collider at the time it was put into the [`CollisionGrid`].
+#[derive(Debug, Clone, PartialEq)]
+struct CellSpan {
+    entity: Entity,
+    columns: Range<usize>,
+    rows: Range<usize>,
+}
+
+/// Uniform grid that allows to efficiently detect collisions
+/// for the [`Collider`] components.
+///
+/// The grid only covers the `bounding_rect` from its [`CollisionGridParams`]:
+/// the colliders partially outside of it are clamped into the border cells and
+/// the colliders completely outside of it are left out. Use the
+/// [`SpatialHash`](super::hash::SpatialHash) when the colliders can be anywhere.
+///
+/// Each cell reserves some spare slots after its entities, so that
+/// the colliders moving between cells can be updated incrementally
+/// without rebuilding the whole grid (see [`CollisionGrid::update_incremental`]),
+/// which pays off when most of the colliders stay in their cells.
+#[derive(Debug, Clone, Default)]
+pub struct CollisionGrid {
+    params: CollisionGridParams,
+    /// The entities of the cells one after another. The spare slots hold arbitrary entities.
+    entities: Vec<Entity>,
+    cell_ranges: Vec<Range<u32>>,
+    cell_ends: Vec<u32>,
+    spans: ComponentPool<CellSpan>,
+    /// A scratch buffer for the colliders removed since the last update.
+    removed: Vec<Entity>,
+    /// The fraction of the colliders that have changed their cells during the last update.
+    moved_fraction: f32,
+}
+
+impl CollisionGrid {
+    /// The fraction of the colliders changing their cells every tick above which
+    /// rebuilding the grid is faster than updating it incrementally.
+    ///
+    /// At the walking speed about 6% of the enemies change their cells every tick and
+    /// the incremental update is slightly faster, while at twice the speed about 11% of them
+    /// do and it's already slower (see `src/bin/collision_bench.rs`).
+    const MOVED_FRACTION_MAX: f32 = 0.08;
+
+    /// Constructs and empty collision grid.
+    pub fn new() -> Self {
+        Default::default()
+    }
+    /// Returns the params from the last time the grid was updated.
+    pub fn params(&self) -> &CollisionGridParams {
+        &self.params
+    }
+    /// Returns the params for the supplied `bounding_rect` with the resolution picked
+    /// automatically (see [`CollisionGridParams::auto`]).
+    ///
+    /// The current resolution is kept while it stays within a factor of two
+    /// of the picked one, so that small changes in the number of colliders
+    /// don't force the grid to be rebuilt.
+    pub fn auto_params(
+        &self,
+        bounding_rect: Rect,
+        colliders: &ComponentPool<Collider>,
+    ) -> CollisionGridParams {
+        let params = CollisionGridParams::auto(bounding_rect, colliders);
+        let current = self.params.resolution;
+        let is_close =
+            |current: usize, picked: usize| current <= picked * 2 && picked <= current * 2;
+        if self.params.bounding_rect == bounding_rect
+            && is_close(current.0, params.resolution.0)
+            && is_close(current.1, params.resolution.1)
+        {
+            return self.params;
+        }
+        params
+    }
+    /// Returns the fraction of the colliders that have changed their cells during the last update.
+    pub fn moved_fraction(&self) -> f32 {
+        self.moved_fraction
+    }
+    /// Returns the total number of entries in all of the cells.
+    ///
+    /// Colliders overlapping several cells are counted once for each cell.
+    pub fn entry_count(&self) -> usize {
+        self.cell_ranges.iter().map(|range| range.len()).sum()
+    }
+    /// Returns the entities that might have collisions in the cell \[`x`, `y`\].
+    ///
+    /// # Panics
+    /// Panics if the `x` or `y` exceeds the current resolution.
+    pub fn cell(&self, x: usize, y: usize) -> &[Entity] {
+        let range = &self.cell_ranges[self.cell_index(x, y)];
+        &self.entities[range.start as usize..range.end as usize]
+    }
+    /// Rebuilds the collision grid with the new parameters.
+    pub fn rebuild(
+        &mut self,
+        transforms: &ComponentPool<Transform>,
+        colliders: &ComponentPool<Collider>,
+        params: CollisionGridParams,
+    ) {
+        self.params = params;
+        let cell_count = params.resolution.0.strict_mul(params.resolution.1);
+
+        // Pass 1: count cells.
+        self.cell_ranges.clear();
+        self.cell_ranges.resize(cell_count, Range::default());
+        let mut moved = 0;
+        for (entity, &collider) in colliders.iter() {
+            let transform = transforms
+                .get(entity)
+                .expect("All colliders must have a Transform component!");
+            let (columns, rows) = self.cell_span(collider.bounds(transform.position));
+            for (x, y) in cells(columns.clone(), rows.clone()) {
+                let index = self.cell_index(x, y);
+                self.cell_ranges[index].end += 1;
+            }
+            let span = CellSpan {
+                entity,
+                columns,
+                rows,
+            };
+            // The old spans are overwritten to find out how many colliders have moved.
+            match self.spans.get_mut(entity) {
+                Some(old_span) => {
+                    moved += (*old_span != span) as usize;
+                    *old_span = span;
+                }
+                None => {
+                    moved += 1;
+                    self.spans.insert(entity, span);
+                }
+            }
+        }
+        self.find_removed(colliders);
+        for &entity in &self.removed {
+            self.spans.remove(entity);
+        }
+        self.moved_fraction = moved as f32 / colliders.len().max(1) as f32;
+        // `self.cell_ranges[i]` is `0..count`, where `count` is number of entities in the cell
+
+        // Build ranges.
+        // Without any entities there is nothing to fill the spare slots with, so they are
+        // left out and the first insertion falls back to rebuilding.
+        let filler = self.spans.values().next().map(|span| span.entity);
+        let mut total: u32 = 0;
+        self.cell_ends.clear();
+        self.cell_ends.resize(cell_count, 0);
+        for index in 0..cell_count {
+            let range = &mut self.cell_ranges[index];
+            let count = range.end;
+            range.start = total;
+            range.end = total;
+            let spare_slots = if filler.is_some() {
+                Self::spare_slots(count)
+            } else {
+                0
+            };
+            total = total
+                .checked_add(count + spare_slots)
+                .expect("Too many collision grid entries!");
+            self.cell_ends[index] = total;
+        }
+        // `self.cell_ranges[i]` is `index..index` where `index` is the future begining of the range.
+        // `self.cell_ends[i]` is the end of the range including the spare slots.
+        // `total` is the total number of slots.
+
+        // Pass 2: fill entities.
+        self.entities.clear();
+        if let Some(filler) = filler {
+            self.entities.resize(total as usize, filler);
+        }
+        for span in self.spans.values() {
+            for (x, y) in cells(span.columns.clone(), span.rows.clone()) {
+                let index = self.cell_index(x, y);
+                let range = &mut self.cell_ranges[index];
+                self.entities[range.end as usize] = span.entity;
+                range.end += 1;
+            }
+        }
+        // `self.cell_ranges[i]` is `start..end` where `start` and `end` indicate the positions
+        // of the corresponding entities within `self.entities`.
+    }
+    /// Updates the collision grid by only moving the colliders that
+    /// have changed their cells since the last update.
+    ///
+    /// Falls back to [`CollisionGrid::rebuild`] if the parameters have changed
+    /// or some cell has ran out of spare slots.
+    pub fn update_incremental(
+        &mut self,
+        transforms: &ComponentPool<Transform>,
+        colliders: &ComponentPool<Collider>,
+        params: CollisionGridParams,
+    ) {
+        if params != self.params {
+            self.rebuild(transforms, colliders, params);
+            return;
+        }
+
+        // Move the colliders that have changed their cells.
+        let mut moved = 0;
+        for (entity, &collider) in colliders.iter() {
+            let transform = transforms
+                .get(entity)
+                .expect("All colliders must have a Transform component!");
+            let (columns, rows) = self.cell_span(collider.bounds(transform.position));
+            let span = CellSpan {
+                entity,
+                columns,
+                rows,
+            };
+            let old_span = self.spans.get(entity).cloned();
+            if old_span.as_ref() == Some(&span) {
+                continue;
+            }
+            moved += 1;
+            if let Some(old_span) = old_span {
+                self.remove_entries(&old_span);
+                self.spans.remove(entity);
+            }
+            if !self.insert_entries(&span) {
+                self.rebuild(transforms, colliders, params);
+                return;
+            }
+            self.spans.insert(entity, span);
+        }
+
+        self.find_removed(colliders);
+        let removed = std::mem::take(&mut self.removed);
+        for &entity in &removed {
+            let span = self.spans.get(entity).unwrap().clone();
+            self.remove_entries(&span);
+            self.spans.remove(entity);
+        }
+        self.removed = removed;
+        self.moved_fraction = moved as f32 / colliders.len().max(1) as f32;
+    }
+    /// Fills the `removed` scratch buffer with the entities
+    /// whose colliders have been removed since the last update.
+    fn find_removed(&mut self, colliders: &ComponentPool<Collider>) {
+        self.removed.clear();
+        // Every collider has a span by now, so the extra spans belong to the removed colliders.
+        if self.spans.len() > colliders.len() {
+            self.removed.extend(
+                self.spans
+                    .values()
+                    .filter(|span| !colliders.contains_entity(span.entity))
+                    .map(|span| span.entity),
+            );
+        }
+    }
+    /// Returns `true` if the cell \[`x`, `y`\] is the first cell shared by the entity
+    /// and the supplied columns and rows.
+    ///
+    /// This allows to visit the entities spanning several cells only once.
+    fn is_first_shared_cell(
+        &self,
+        entity: Entity,
+        columns: &Range<usize>,
+        rows: &Range<usize>,
+        x: usize,
+        y: usize,
+    ) -> bool {
+        let span = self.spans.get(entity).unwrap();
+        let first = (
+            span.columns.start.max(columns.start),
+            span.rows.start.max(rows.start),
+        );
+        first == (x, y)
+    }
+    /// Returns the number of spare slots reserved after `count` entities in a cell.
+    fn spare_slots(count: u32) -> u32 {
+        count / 2 + 2
+    }
+    /// Removes the entity from the cells of the span.
+    fn remove_entries(&mut self, span: &CellSpan) {
+        for (x, y) in cells(span.columns.clone(), span.rows.clone()) {
+            let index = self.cell_index(x, y);
+            let position = self
+                .cell(x, y)
+                .iter()
+                .position(|&other| other == span.entity)
+                .expect("Entity must be in the cells of its span!");
+            let range = &mut self.cell_ranges[index];
+            range.end -= 1;
+            let last = range.end as usize;
+            self.entities.swap(range.start as usize + position, last);
+        }
+    }
+    /// Adds the entity to the cells of the span and returns `true` on success
+    /// or `false` if some of the cells have ran out of spare slots.
+    fn insert_entries(&mut self, span: &CellSpan) -> bool {
+        for (x, y) in cells(span.columns.clone(), span.rows.clone()) {
+            let index = self.cell_index(x, y);
+            let range = &mut self.cell_ranges[index];
+            if range.end == self.cell_ends[index] {
+                return false;
+            }
+            self.entities[range.end as usize] = span.entity;
+            range.end += 1;
+        }
+        true
+    }
+    /// Returns the index in the cell array for the specified point.
+    ///
+    /// # Panics
+    /// Panics if the point is out of bounds.
+    fn cell_index(&self, x: usize, y: usize) -> usize {
+        let res = self.params.resolution;
+        assert!(x < res.0 && y < res.1, "The cell index is out of bounds!");
+        y * res.0 + x
+    }
+    /// Returns the size of a single cell.
+    fn cell_size(&self) -> Vec2 {
+        let res = Vec2::new(
+            self.params.resolution.0 as f32,
+            self.params.resolution.1 as f32,
+        );
+        self.params.bounding_rect.size() / res
+    }
+    /// Returns the ranges of cell columns and rows that overlap the area.
+    fn cell_span(&self, area: Rect) -> (Range<usize>, Range<usize>) {
+        let res = Vec2::new(
+            self.params.resolution.0 as f32,
+            self.params.resolution.1 as f32,
+        );
+        let unit = self.cell_size();
+        let origin = self.params.bounding_rect.point();
+        let local_min = ((area.point() - origin) / unit).floor().max(Vec2::ZERO);
+        let local_max = ((area.point() + area.size() - origin) / unit)
+            .ceil()
+            .min(res);
+        let range_x = Range {
+            start: local_min.x as usize,
+            end: local_max.x as usize,
+        };
+        let range_y = Range {
+            start: local_min.y as usize,
+            end: local_max.y as usize,
+        };
+        (range_x, range_y)
+    }
+}
+
+impl SpatialIndex for CollisionGrid {
+    /// Updates the grid with the automatically picked resolution (see [`CollisionGrid::auto_params`]).
+    ///
+    /// The grid is updated incrementally unless too many colliders have changed
+    /// their cells during the last update (see [`CollisionGrid::MOVED_FRACTION_MAX`]),
+    /// in which case it's rebuilt.
+    fn update(
+        &mut self,
+        transforms: &ComponentPool<Transform>,
+        colliders: &ComponentPool<Collider>,
+        bounding_rect: Rect,
+    ) {
+        let params = self.auto_params(bounding_rect, colliders);
+        if self.moved_fraction > Self::MOVED_FRACTION_MAX {
+            self.rebuild(transforms, colliders, params);
+        } else {
+            self.update_incremental(transforms, colliders, params);
+        }
+    }
+    fn query_area(&self, area: Rect, out: &mut Vec<Entity>) {
+        let (columns, rows) = self.cell_span(area);
+        for (x, y) in cells(columns.clone(), rows.clone()) {
+            for &entity in self.cell(x, y) {
+                if self.is_first_shared_cell(entity, &columns, &rows, x, y) {
+                    out.push(entity);
+                }
+            }
+        }
+    }
+    fn candidate_pairs(&self, out: &mut Vec<(Entity, Entity)>) {
+        let res = self.params.resolution;
+        for (x, y) in cells(0..res.0, 0..res.1) {
+            let cell = self.cell(x, y);
+            for (i, &entity) in cell.iter().enumerate() {
+                let span = self.spans.get(entity).unwrap();
+                for &other_entity in &cell[i + 1..] {
+                    if self.is_first_shared_cell(other_entity, &span.columns, &span.rows, x, y) {
+                        out.push((entity, other_entity));
+                    }
+                }
+            }
+        }
+    }
+    fn debug_cells(&self, out: &mut Vec<(Rect, usize)>) {
+        let (res, size) = (self.params.resolution, self.cell_size());
+        let origin = self.params.bounding_rect.point();
+        for (x, y) in cells(0..res.0, 0..res.1) {
+            let point = origin + Vec2::new(x as f32, y as f32) * size;
+            out.push((
+                Rect::new(point.x, point.y, size.x, size.y),
+                self.cell(x, y).len(),
+            ));
+        }
+    }
+    /// Searches the cells ring by ring outward from the cell containing `position`
+    /// and stops as soon as no unvisited cell can contain a closer entity.
+    ///
+    /// Unlike the default search, every cell is visited at most once.
+    fn nearest(
+        &self,
+        transforms: &ComponentPool<Transform>,
+        colliders: &ComponentPool<Collider>,
+        position: Vec2,
+        mask: CollisionMask,
+        max_dist: f32,
+        k: usize,
+    ) -> Vec<Entity> {
+        let mut found: Vec<(f32, Entity)> = Vec::new();
+        let res = self.params.resolution;
+        if k == 0 || res.0 == 0 || res.1 == 0 {
+            return Vec::new();
+        }
+        let unit = self.cell_size();
+        let min_unit = unit.x.min(unit.y);
+        let local = ((position - self.params.bounding_rect.point()) / unit).floor();
+        let cx = (local.x.max(0.) as usize).min(res.0 - 1);
+        let cy = (local.y.max(0.) as usize).min(res.1 - 1);
+        let max_ring = cx.max(res.0 - 1 - cx).max(cy).max(res.1 - 1 - cy);
+        for ring in 0..=max_ring {
+            // Every cell in this ring is at least `(ring - 1) * min_unit` away.
+            let ring_dist = ring.saturating_sub(1) as f32 * min_unit;
+            if ring_dist > max_dist || (found.len() >= k && ring_dist > found[k - 1].0) {
+                break;
+            }
+            for (x, y) in ring_cells(cx, cy, ring, res) {
+                for &entity in self.cell(x, y) {
+                    let Some(collider) = colliders.get(entity) else {
+                        continue;
+                    };
+                    if !collider.monitorable.overlaps(mask) {
+                        continue;
+                    }
+                    let dist = transforms.get(entity).unwrap().position.distance(position);
+                    if dist > max_dist || found.iter().any(|&(_, other)| other == entity) {
+                        continue;
+                    }
+                    let index = found.partition_point(|&(other, _)| other <= dist);
+                    if index < k {
+                        found.insert(index, (dist, entity));
+                        found.truncate(k);
+                    }
+                }
+            }
+        }
+        found.into_iter().map(|(_, entity)| entity).collect()
+    }
+}
+
+/// Returns an iterator over the cells in the supplied columns and rows.
+fn cells(columns: Range<usize>, rows: Range<usize>) -> impl Iterator<Item = (usize, usize)> {
+    rows.flat_map(move |y| columns.clone().map(move |x| (x, y)))
+}
+
+/// Returns an iterator over the cells at the Chebyshev distance `ring` from the cell
+/// \[`cx`, `cy`\] that lie within the resolution `res`.
+fn ring_cells(
+    cx: usize,
+    cy: usize,
+    ring: usize,
+    res: (usize, usize),
+) -> impl Iterator<Item = (usize, usize)> {
+    let (cx, cy, ring) = (cx as isize, cy as isize, ring as isize);
+    let (min_x, max_x) = (cx - ring, cx + ring);
+    let (min_y, max_y) = (cy - ring, cy + ring);
+    (min_y..=max_y)
+        .flat_map(move |y| {
+            let step = if y == min_y || y == max_y {
+                1
+            } else {
+                (max_x - min_x).max(1)
+            };
+            (min_x..=max_x).step_by(step as usize).map(move |x| (x, y))
+        })
+        .filter(move |&(x, y)| x >= 0 && y >= 0 && (x as usize) < res.0 && (y as usize) < res.1)
+        .map(|(x, y)| (x as usize, y as usize))
+}
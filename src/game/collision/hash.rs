@@ -0,0 +1,192 @@
+use std::{collections::HashMap, ops::Range};
+
+use macroquad::math::{Rect, Vec2};
+
+use crate::{
+    ecs::{component::ComponentPool, entity::Entity},
+    game::{
+        collision::{Collider, Shape, SpatialIndex},
+        transform::Transform,
+    },
+};
+
+/// The cells overlapped by the collider at the time it was put into the [`SpatialHash`].
+#[derive(Debug, Clone)]
+struct CellSpan {
+    columns: Range<i32>,
+    rows: Range<i32>,
+}
+
+/// Hashed grid that allows to efficiently detect collisions
+/// for the [`Collider`] components.
+///
+/// Unlike the [`CollisionGrid`](super::grid::CollisionGrid), the cells are keyed by their
+/// integer coordinates and only exist while they have some colliders in them, so the
+/// colliders anywhere in the world are spread between cells equally well.
+#[derive(Debug, Clone, Default)]
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<Entity>>,
+    spans: ComponentPool<CellSpan>,
+    /// The span covering all of the colliders or `None` if there are none.
+    bounds: Option<CellSpan>,
+}
+
+impl SpatialHash {
+    /// The size of a cell relative to the average collider radius.
+    const CELL_SIZE_PER_RADIUS: f32 = 4.;
+    /// The cell size used when there are no colliders.
+    const CELL_SIZE_DEFAULT: f32 = 64.;
+
+    /// Constructs an empty spatial hash.
+    pub fn new() -> Self {
+        Default::default()
+    }
+    /// Returns the size of the cells from the last time the hash was updated.
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+    /// Returns the entities that might have collisions in the cell \[`x`, `y`\].
+    pub fn cell(&self, x: i32, y: i32) -> &[Entity] {
+        self.cells.get(&(x, y)).map_or(&[], Vec::as_slice)
+    }
+    /// Returns the ranges of cell columns and rows that overlap the area.
+    fn cell_span(&self, area: Rect) -> CellSpan {
+        let min = (area.point() / self.cell_size).floor();
+        let max = ((area.point() + area.size()) / self.cell_size).floor() + Vec2::ONE;
+        CellSpan {
+            columns: min.x as i32..max.x as i32,
+            rows: min.y as i32..max.y as i32,
+        }
+    }
+    /// Returns `true` if the cell \[`x`, `y`\] is the first cell shared by the entity
+    /// and the supplied span.
+    ///
+    /// This allows to visit the entities spanning several cells only once.
+    fn is_first_shared_cell(&self, entity: Entity, span: &CellSpan, x: i32, y: i32) -> bool {
+        let other_span = self.spans.get(entity).unwrap();
+        let first = (
+            other_span.columns.start.max(span.columns.start),
+            other_span.rows.start.max(span.rows.start),
+        );
+        first == (x, y)
+    }
+}
+
+impl SpatialIndex for SpatialHash {
+    /// Rebuilds the hash with the cell size picked from the average collider radius.
+    ///
+    /// The `bounding_rect` is ignored.
+    fn update(
+        &mut self,
+        transforms: &ComponentPool<Transform>,
+        colliders: &ComponentPool<Collider>,
+        _bounding_rect: Rect,
+    ) {
+        self.cell_size = if colliders.is_empty() {
+            Self::CELL_SIZE_DEFAULT
+        } else {
+            let radius_total: f32 = colliders
+                .values()
+                .map(|collider| {
+                    let Shape::Circle { radius } = collider.shape;
+                    radius
+                })
+                .sum();
+            (radius_total / colliders.len() as f32 * Self::CELL_SIZE_PER_RADIUS).max(1.)
+        };
+        // Keep the allocations of the cells that are still in use.
+        self.cells.retain(|_, cell| {
+            let is_used = !cell.is_empty();
+            cell.clear();
+            is_used
+        });
+        self.spans.clear();
+        self.bounds = None;
+        for (entity, collider) in colliders.iter() {
+            let transform = transforms
+                .get(entity)
+                .expect("All colliders must have a Transform component!");
+            let span = self.cell_span(collider.bounds(transform.position));
+            for y in span.rows.clone() {
+                for x in span.columns.clone() {
+                    self.cells.entry((x, y)).or_default().push(entity);
+                }
+            }
+            self.bounds = Some(match self.bounds.take() {
+                Some(bounds) => CellSpan {
+                    columns: bounds.columns.start.min(span.columns.start)
+                        ..bounds.columns.end.max(span.columns.end),
+                    rows: bounds.rows.start.min(span.rows.start)
+                        ..bounds.rows.end.max(span.rows.end),
+                },
+                None => span.clone(),
+            });
+            self.spans.insert(entity, span);
+        }
+    }
+    /// Visits the cells in the queried span or, if the span has more cells
+    /// than there are in the hash, the cells of the hash within the span.
+    fn query_area(&self, area: Rect, out: &mut Vec<Entity>) {
+        let Some(bounds) = &self.bounds else {
+            return;
+        };
+        let span = self.cell_span(area);
+        // No colliders are outside of the bounds, so neither are their cells.
+        let span = CellSpan {
+            columns: span.columns.start.max(bounds.columns.start)
+                ..span.columns.end.min(bounds.columns.end),
+            rows: span.rows.start.max(bounds.rows.start)..span.rows.end.min(bounds.rows.end),
+        };
+        if span.columns.is_empty() || span.rows.is_empty() {
+            return;
+        }
+        let mut visit = |x, y, cell: &[Entity]| {
+            for &entity in cell {
+                if self.is_first_shared_cell(entity, &span, x, y) {
+                    out.push(entity);
+                }
+            }
+        };
+        let cell_count = (span.columns.end as i64 - span.columns.start as i64)
+            * (span.rows.end as i64 - span.rows.start as i64);
+        if cell_count > self.cells.len() as i64 {
+            for (&(x, y), cell) in &self.cells {
+                if span.columns.contains(&x) && span.rows.contains(&y) {
+                    visit(x, y, cell);
+                }
+            }
+        } else {
+            for y in span.rows.clone() {
+                for x in span.columns.clone() {
+                    visit(x, y, self.cell(x, y));
+                }
+            }
+        }
+    }
+    fn candidate_pairs(&self, out: &mut Vec<(Entity, Entity)>) {
+        for (&(x, y), cell) in &self.cells {
+            for (i, &entity) in cell.iter().enumerate() {
+                let span = self.spans.get(entity).unwrap();
+                for &other_entity in &cell[i + 1..] {
+                    if self.is_first_shared_cell(other_entity, span, x, y) {
+                        out.push((entity, other_entity));
+                    }
+                }
+            }
+        }
+    }
+    fn debug_cells(&self, out: &mut Vec<(Rect, usize)>) {
+        for (&(x, y), cell) in &self.cells {
+            out.push((
+                Rect::new(
+                    x as f32 * self.cell_size,
+                    y as f32 * self.cell_size,
+                    self.cell_size,
+                    self.cell_size,
+                ),
+                cell.len(),
+            ));
+        }
+    }
+}
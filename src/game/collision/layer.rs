@@ -0,0 +1,122 @@
+use std::ops::BitOr;
+
+use strum::{EnumCount, EnumIter, IntoEnumIterator};
+
+/// A named layer within collision system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumCount, EnumIter)]
+pub enum CollisionLayer {
+    /// The player character.
+    Player,
+    /// The enemies.
+    Enemy,
+    /// The bullets shot by the player.
+    PlayerProjectile,
+    /// The bullets shot by the enemies.
+    EnemyProjectile,
+    /// The things the player can pick up.
+    Pickup,
+    /// The static level geometry.
+    Wall,
+    /// The areas that detect entities without blocking them.
+    Trigger,
+}
+
+const _: () = assert!(CollisionLayer::COUNT <= u32::BITS as usize);
+
+impl CollisionLayer {
+    /// Returns the mask containing only this layer.
+    pub const fn mask(self) -> CollisionMask {
+        CollisionMask(1 << self as u32)
+    }
+}
+
+/// A mask determining the layers within collision system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub struct CollisionMask(pub u32);
+
+impl CollisionMask {
+    /// The mask without any layers.
+    pub const NONE: Self = Self(0);
+
+    /// Returns `true` if the two collision masks have
+    /// overlapping layers.
+    pub fn overlaps(self, other: Self) -> bool {
+        (self.0 & other.0) != 0
+    }
+    /// Returns `true` if the mask contains the layer.
+    pub fn contains(self, layer: CollisionLayer) -> bool {
+        self.overlaps(layer.mask())
+    }
+    /// Returns an iterator over the layers in the mask.
+    pub fn layers(self) -> impl Iterator<Item = CollisionLayer> {
+        CollisionLayer::iter().filter(move |&layer| self.contains(layer))
+    }
+}
+
+impl From<CollisionLayer> for CollisionMask {
+    fn from(value: CollisionLayer) -> Self {
+        value.mask()
+    }
+}
+
+impl BitOr<Self> for CollisionMask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// Determines which layers are allowed to interact with each other.
+///
+/// The colliders only collide if their masks say so and
+/// the matrix allows some pair of their layers to interact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CollisionMatrix([CollisionMask; CollisionLayer::COUNT]);
+
+impl CollisionMatrix {
+    /// Constructs a matrix where none of the layers interact.
+    pub const fn new() -> Self {
+        Self([CollisionMask::NONE; CollisionLayer::COUNT])
+    }
+    /// Allows the two layers to interact with each other.
+    pub const fn allow(mut self, layer: CollisionLayer, other: CollisionLayer) -> Self {
+        self.0[layer as usize].0 |= other.mask().0;
+        self.0[other as usize].0 |= layer.mask().0;
+        self
+    }
+    /// Returns the layers that the layer is allowed to interact with.
+    pub fn interactions(&self, layer: CollisionLayer) -> CollisionMask {
+        self.0[layer as usize]
+    }
+    /// Returns `true` if some of the layers of `mask` are allowed
+    /// to interact with some of the layers of `other`.
+    pub fn allows(&self, mask: CollisionMask, other: CollisionMask) -> bool {
+        mask.layers()
+            .any(|layer| self.interactions(layer).overlaps(other))
+    }
+}
+
+impl Default for CollisionMatrix {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The interactions between the layers in the game.
+pub const COLLISION_MATRIX: CollisionMatrix = {
+    use CollisionLayer::*;
+    CollisionMatrix::new()
+        .allow(Player, Enemy)
+        .allow(Player, EnemyProjectile)
+        .allow(Player, Pickup)
+        .allow(Player, Wall)
+        .allow(Player, Trigger)
+        .allow(Enemy, Enemy)
+        .allow(Enemy, PlayerProjectile)
+        .allow(Enemy, Wall)
+        .allow(Enemy, Trigger)
+        .allow(PlayerProjectile, Wall)
+        .allow(EnemyProjectile, Wall)
+        .allow(Pickup, Trigger)
+};
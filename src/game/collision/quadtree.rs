@@ -0,0 +1,274 @@
+use macroquad::math::Rect;
+
+use crate::{
+    ecs::{component::ComponentPool, entity::Entity},
+    game::{
+        collision::{Collider, SpatialIndex},
+        transform::Transform,
+    },
+};
+
+/// A node of the [`LooseQuadtree`].
+#[derive(Debug, Clone)]
+struct Node {
+    /// The area of the node without the loose margins.
+    bounds: Rect,
+    /// The index of the first of the four consecutive children if the node has any.
+    children: Option<usize>,
+    /// The indices of the entries stored in this node.
+    entries: Vec<usize>,
+    /// The number of entries stored in this node and its descendants.
+    count: usize,
+    /// The bounding box of the entries stored in this node and its descendants.
+    ///
+    /// Meaningless while the `count` is zero.
+    tight_bounds: Rect,
+}
+
+impl Node {
+    fn new(bounds: Rect) -> Self {
+        Self {
+            bounds,
+            children: None,
+            entries: Vec::new(),
+            count: 0,
+            tight_bounds: bounds,
+        }
+    }
+    /// Accounts for the entry with the bounding box stored in this node or its descendants.
+    fn add(&mut self, bounds: Rect) {
+        self.tight_bounds = if self.count == 0 {
+            bounds
+        } else {
+            self.tight_bounds.combine_with(bounds)
+        };
+        self.count += 1;
+    }
+    /// Returns `true` if some entries of the node or its descendants might overlap the `area`.
+    ///
+    /// The entries stick out of the node by at most half of its size, but the subtrees
+    /// are culled by the bounding boxes of their actual entries, which are usually much tighter.
+    fn might_overlap(&self, area: &Rect) -> bool {
+        self.count != 0 && self.tight_bounds.overlaps(area)
+    }
+}
+
+/// Loose quadtree that allows to efficiently detect collisions
+/// for the [`Collider`] components.
+///
+/// Each collider is stored in the deepest node that is at least as large as the collider
+/// and contains its center, so the colliders never have to be split between nodes.
+/// This adapts to uneven density better than a uniform grid.
+#[derive(Debug, Clone, Default)]
+pub struct LooseQuadtree {
+    nodes: Vec<Node>,
+    /// The entities stored in the tree alongside their bounding boxes.
+    entries: Vec<(Entity, Rect)>,
+}
+
+impl LooseQuadtree {
+    /// The maximal depth of the tree.
+    const DEPTH_MAX: usize = 8;
+
+    /// Constructs an empty loose quadtree.
+    pub fn new() -> Self {
+        Default::default()
+    }
+    /// Inserts the entity with the supplied bounding box into the tree.
+    fn insert(&mut self, entity: Entity, bounds: Rect) {
+        let center = bounds.center();
+        let size = bounds.w.max(bounds.h);
+        let mut index = 0;
+        // The colliders outside of the root stay in the root.
+        self.nodes[index].add(bounds);
+        if self.nodes[index].bounds.contains(center) {
+            for _ in 0..Self::DEPTH_MAX {
+                let node_bounds = self.nodes[index].bounds;
+                if node_bounds.w.min(node_bounds.h) / 2. < size {
+                    break;
+                }
+                let first_child = self.children(index);
+                let half = node_bounds.center();
+                let quadrant = (center.x >= half.x) as usize + 2 * (center.y >= half.y) as usize;
+                index = first_child + quadrant;
+                self.nodes[index].add(bounds);
+            }
+        }
+        self.nodes[index].entries.push(self.entries.len());
+        self.entries.push((entity, bounds));
+    }
+    /// Returns the index of the first child of the node, creating the children if needed.
+    fn children(&mut self, index: usize) -> usize {
+        if let Some(first_child) = self.nodes[index].children {
+            return first_child;
+        }
+        let bounds = self.nodes[index].bounds;
+        let (w, h) = (bounds.w / 2., bounds.h / 2.);
+        let first_child = self.nodes.len();
+        for (x, y) in [(0., 0.), (1., 0.), (0., 1.), (1., 1.)] {
+            self.nodes.push(Node::new(Rect::new(
+                bounds.x + x * w,
+                bounds.y + y * h,
+                w,
+                h,
+            )));
+        }
+        self.nodes[index].children = Some(first_child);
+        first_child
+    }
+    /// Calls `f` with the index of every entry in the subtree of the node
+    /// whose bounding box overlaps with the `area`.
+    fn visit_area(&self, root: usize, area: Rect, mut f: impl FnMut(usize)) {
+        // Each level can add at most four nodes to the stack while popping one.
+        let mut stack = [root; 3 * Self::DEPTH_MAX + 1];
+        let mut len = 1;
+        while len > 0 {
+            len -= 1;
+            let node = &self.nodes[stack[len]];
+            for &entry in &node.entries {
+                if self.entries[entry].1.overlaps(&area) {
+                    f(entry);
+                }
+            }
+            let Some(first_child) = node.children else {
+                continue;
+            };
+            for child in first_child..first_child + 4 {
+                let child_node = &self.nodes[child];
+                if child_node.might_overlap(&area) {
+                    stack[len] = child;
+                    len += 1;
+                }
+            }
+        }
+    }
+    /// Appends the pairs of the overlapping entries within the subtree of the node.
+    fn subtree_pairs(&self, index: usize, out: &mut Vec<(Entity, Entity)>) {
+        let node = &self.nodes[index];
+        for (i, &entry) in node.entries.iter().enumerate() {
+            let (entity, bounds) = self.entries[entry];
+            for &other in &node.entries[i + 1..] {
+                let (other_entity, other_bounds) = self.entries[other];
+                if bounds.overlaps(&other_bounds) {
+                    out.push((entity, other_entity));
+                }
+            }
+        }
+        let Some(first_child) = node.children else {
+            return;
+        };
+        let children = first_child..first_child + 4;
+        for child in children.clone() {
+            if self.nodes[child].count == 0 {
+                continue;
+            }
+            for &entry in &node.entries {
+                self.entry_pairs(entry, child, out);
+            }
+            self.subtree_pairs(child, out);
+            for other_child in child + 1..children.end {
+                self.cross_pairs(child, other_child, out);
+            }
+        }
+    }
+    /// Appends the pairs of the overlapping entries from the two disjoint subtrees.
+    fn cross_pairs(&self, index: usize, other_index: usize, out: &mut Vec<(Entity, Entity)>) {
+        let (node, other_node) = (&self.nodes[index], &self.nodes[other_index]);
+        if !node.might_overlap(&other_node.tight_bounds) || other_node.count == 0 {
+            return;
+        }
+        // Descend both subtrees at once, so that only the neighbouring nodes get paired.
+        for &entry in &node.entries {
+            let (entity, bounds) = self.entries[entry];
+            for &other in &other_node.entries {
+                let (other_entity, other_bounds) = self.entries[other];
+                if bounds.overlaps(&other_bounds) {
+                    out.push((entity, other_entity));
+                }
+            }
+            if let Some(first_child) = other_node.children {
+                for child in first_child..first_child + 4 {
+                    self.entry_pairs(entry, child, out);
+                }
+            }
+        }
+        let Some(first_child) = node.children else {
+            return;
+        };
+        for child in first_child..first_child + 4 {
+            for &other in &other_node.entries {
+                self.entry_pairs(other, child, out);
+            }
+            if let Some(other_first_child) = other_node.children {
+                for other_child in other_first_child..other_first_child + 4 {
+                    self.cross_pairs(child, other_child, out);
+                }
+            }
+        }
+    }
+    /// Appends the pairs of the entry with the overlapping entries in the subtree of the node.
+    fn entry_pairs(&self, entry: usize, index: usize, out: &mut Vec<(Entity, Entity)>) {
+        let (entity, bounds) = self.entries[entry];
+        if !self.nodes[index].might_overlap(&bounds) {
+            return;
+        }
+        self.visit_area(index, bounds, |other| {
+            out.push((entity, self.entries[other].0));
+        });
+    }
+}
+
+impl SpatialIndex for LooseQuadtree {
+    /// Rebuilds the tree with the root covering the `bounding_rect`.
+    ///
+    /// The nodes are kept while the `bounding_rect` stays the same, so that their entry
+    /// lists are reused. The nodes left without entries are skipped by the queries.
+    fn update(
+        &mut self,
+        transforms: &ComponentPool<Transform>,
+        colliders: &ComponentPool<Collider>,
+        bounding_rect: Rect,
+    ) {
+        if self
+            .nodes
+            .first()
+            .is_some_and(|root| root.bounds == bounding_rect)
+        {
+            for node in &mut self.nodes {
+                node.entries.clear();
+                node.count = 0;
+            }
+        } else {
+            self.nodes.clear();
+            self.nodes.push(Node::new(bounding_rect));
+        }
+        self.entries.clear();
+        for (entity, collider) in colliders.iter() {
+            let transform = transforms
+                .get(entity)
+                .expect("All colliders must have a Transform component!");
+            self.insert(entity, collider.bounds(transform.position));
+        }
+    }
+    fn query_area(&self, area: Rect, out: &mut Vec<Entity>) {
+        if !self.nodes.is_empty() {
+            self.visit_area(0, area, |entry| out.push(self.entries[entry].0));
+        }
+    }
+    /// Walks the tree pairing the entries of every node with the entries below it
+    /// and the subtrees of the siblings with each other, since the entries
+    /// of the neighbouring nodes stick out of them and can overlap.
+    fn candidate_pairs(&self, out: &mut Vec<(Entity, Entity)>) {
+        if !self.nodes.is_empty() {
+            self.subtree_pairs(0, out);
+        }
+    }
+    fn debug_cells(&self, out: &mut Vec<(Rect, usize)>) {
+        out.extend(
+            self.nodes
+                .iter()
+                .filter(|node| node.count != 0)
+                .map(|node| (node.bounds, node.entries.len())),
+        );
+    }
+}
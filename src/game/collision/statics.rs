@@ -0,0 +1,363 @@
+use std::ops::Range;
+
+use macroquad::math::{Rect, Vec2};
+
+use crate::game::collision::{
+    Collider, Shape,
+    layer::{COLLISION_MATRIX, CollisionLayer},
+};
+
+/// A shape of the static level geometry.
+#[derive(Debug, Clone)]
+pub enum StaticShape {
+    /// An axis-aligned rectangle.
+    Rect(Rect),
+    /// A convex polygon.
+    Polygon(Box<[Vec2]>),
+}
+
+impl StaticShape {
+    /// Constructs a convex polygon from its vertices.
+    ///
+    /// # Panics
+    /// Panics if there are less than 3 vertices, all of the vertices lie
+    /// on the same line or the polygon is not convex.
+    pub fn polygon(vertices: impl Into<Box<[Vec2]>>) -> Self {
+        let vertices = vertices.into();
+        assert!(
+            vertices.len() >= 3,
+            "Polygon must have at least 3 vertices!"
+        );
+        let area = signed_area(&vertices);
+        assert!(area.abs() > f32::EPSILON, "Polygon must not be degenerate!");
+        let winding = area.signum();
+        let is_convex = (0..vertices.len()).all(|i| {
+            let (a, b, c) = (
+                vertices[i],
+                vertices[(i + 1) % vertices.len()],
+                vertices[(i + 2) % vertices.len()],
+            );
+            (b - a).perp_dot(c - b) * winding >= 0.
+        });
+        assert!(is_convex, "Polygon must be convex!");
+        Self::Polygon(vertices)
+    }
+    /// Returns the rectangles covering the solid tiles of a tile map.
+    ///
+    /// Each row of the tile map yields `true` for a solid tile and `false` for an empty one.
+    /// The consecutive solid tiles in a row are merged into a single rectangle.
+    /// The top left corner of the first tile is at `origin`.
+    pub fn tiles(
+        origin: Vec2,
+        tile_size: f32,
+        rows: impl IntoIterator<Item = impl IntoIterator<Item = bool>>,
+    ) -> impl Iterator<Item = Self> {
+        rows.into_iter().enumerate().flat_map(move |(y, row)| {
+            let mut runs = Vec::new();
+            let mut start = None;
+            for (x, is_solid) in row.into_iter().chain(std::iter::once(false)).enumerate() {
+                match (is_solid, start) {
+                    (true, None) => start = Some(x),
+                    (false, Some(first)) => {
+                        runs.push(first..x);
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+            runs.into_iter().map(move |run| {
+                Self::Rect(Rect::new(
+                    origin.x + run.start as f32 * tile_size,
+                    origin.y + y as f32 * tile_size,
+                    run.len() as f32 * tile_size,
+                    tile_size,
+                ))
+            })
+        })
+    }
+    /// Returns the bounding box of the shape.
+    pub fn bounds(&self) -> Rect {
+        match self {
+            Self::Rect(rect) => *rect,
+            Self::Polygon(vertices) => {
+                let min = vertices.iter().copied().reduce(Vec2::min).unwrap();
+                let max = vertices.iter().copied().reduce(Vec2::max).unwrap();
+                Rect::new(min.x, min.y, max.x - min.x, max.y - min.y)
+            }
+        }
+    }
+    /// Returns how far the circle has to be moved to stop overlapping
+    /// the shape or `None` if they don't overlap.
+    fn penetration(&self, center: Vec2, radius: f32) -> Option<Vec2> {
+        match self {
+            Self::Rect(rect) => {
+                let corners = [
+                    rect.point(),
+                    Vec2::new(rect.right(), rect.top()),
+                    rect.point() + rect.size(),
+                    Vec2::new(rect.left(), rect.bottom()),
+                ];
+                polygon_penetration(&corners, center, radius)
+            }
+            Self::Polygon(vertices) => polygon_penetration(vertices, center, radius),
+        }
+    }
+}
+
+/// Static level geometry that blocks the colliders on the [`CollisionLayer::Wall`] layer.
+///
+/// Unlike the [`SpatialIndex`](super::SpatialIndex) implementations, which are
+/// updated every tick, the shapes are put into a uniform grid once on construction
+/// and never move afterwards.
+#[derive(Debug, Clone, Default)]
+pub struct StaticGeometry {
+    shapes: Vec<StaticShape>,
+    bounding_rect: Rect,
+    cell_size: f32,
+    resolution: (usize, usize),
+    /// The ranges in `indices` for each cell.
+    cells: Vec<Range<u32>>,
+    /// The indices of the shapes overlapping each cell.
+    indices: Vec<u32>,
+}
+
+impl StaticGeometry {
+    /// The number of times the colliders are pushed out of the geometry,
+    /// so that they don't end up inside of a neighbouring shape.
+    const PUSH_OUT_ITERATIONS: usize = 4;
+
+    /// Constructs the geometry from the shapes partitioned into square cells of `cell_size`.
+    ///
+    /// # Panics
+    /// Panics if `cell_size` is not positive.
+    pub fn new(shapes: Vec<StaticShape>, cell_size: f32) -> Self {
+        assert!(cell_size > 0., "Cell size must be positive!");
+        let Some(bounding_rect) = shapes
+            .iter()
+            .map(StaticShape::bounds)
+            .reduce(|rect, other| rect.combine_with(other))
+        else {
+            return Self {
+                cell_size,
+                ..Default::default()
+            };
+        };
+        let resolution = (
+            ((bounding_rect.w / cell_size).ceil() as usize).max(1),
+            ((bounding_rect.h / cell_size).ceil() as usize).max(1),
+        );
+        let mut res = Self {
+            shapes,
+            bounding_rect,
+            cell_size,
+            resolution,
+            cells: Vec::new(),
+            indices: Vec::new(),
+        };
+        let mut counts = vec![0u32; resolution.0 * resolution.1];
+        for shape in &res.shapes {
+            let (columns, rows) = res.cell_span(shape.bounds()).unwrap();
+            for y in rows {
+                for x in columns.clone() {
+                    counts[y * resolution.0 + x] += 1;
+                }
+            }
+        }
+        let mut start = 0;
+        res.cells = counts
+            .iter()
+            .map(|&count| {
+                let range = start..start;
+                start += count;
+                range
+            })
+            .collect();
+        res.indices = vec![0; start as usize];
+        for (index, shape) in res.shapes.iter().enumerate() {
+            let (columns, rows) = res.cell_span(shape.bounds()).unwrap();
+            for y in rows {
+                for x in columns.clone() {
+                    let cell = &mut res.cells[y * resolution.0 + x];
+                    res.indices[cell.end as usize] = index as u32;
+                    cell.end += 1;
+                }
+            }
+        }
+        res
+    }
+    /// Returns the shapes of the geometry.
+    pub fn shapes(&self) -> &[StaticShape] {
+        &self.shapes
+    }
+    /// Returns `true` if the collider is blocked by the geometry at the supplied position.
+    pub fn blocks(&self, collider: &Collider, position: Vec2) -> bool {
+        if !Self::is_blocked(collider) {
+            return false;
+        }
+        let Shape::Circle { radius } = collider.shape;
+        let mut found = false;
+        self.visit_area(collider.bounds(position), |shape| {
+            found = found || shape.penetration(position, radius).is_some();
+        });
+        found
+    }
+    /// Returns the closest position to the supplied one where
+    /// the collider is not blocked by the geometry.
+    pub fn push_out(&self, collider: &Collider, mut position: Vec2) -> Vec2 {
+        if !Self::is_blocked(collider) {
+            return position;
+        }
+        let Shape::Circle { radius } = collider.shape;
+        for _ in 0..Self::PUSH_OUT_ITERATIONS {
+            let mut is_pushed = false;
+            self.visit_area(collider.bounds(position), |shape| {
+                if let Some(push) = shape.penetration(position, radius) {
+                    position += push;
+                    is_pushed = true;
+                }
+            });
+            if !is_pushed {
+                break;
+            }
+        }
+        position
+    }
+    /// Returns `true` if the layers of the collider are allowed to interact with the walls.
+    fn is_blocked(collider: &Collider) -> bool {
+        COLLISION_MATRIX.allows(collider.monitorable, CollisionLayer::Wall.mask())
+    }
+    /// Returns the ranges of cell columns and rows that overlap the area
+    /// or `None` if the area is outside of the geometry.
+    fn cell_span(&self, area: Rect) -> Option<(Range<usize>, Range<usize>)> {
+        if !area.overlaps(&self.bounding_rect) {
+            return None;
+        }
+        let cell = |value: f32, min: f32, len: usize| {
+            (((value - min) / self.cell_size) as usize).min(len - 1)
+        };
+        let (x, y) = (self.bounding_rect.x, self.bounding_rect.y);
+        Some((
+            cell(area.left().max(x), x, self.resolution.0)
+                ..cell(area.right(), x, self.resolution.0) + 1,
+            cell(area.top().max(y), y, self.resolution.1)
+                ..cell(area.bottom(), y, self.resolution.1) + 1,
+        ))
+    }
+    /// Calls `f` once for each shape whose bounding box might overlap with the area.
+    fn visit_area(&self, area: Rect, mut f: impl FnMut(&StaticShape)) {
+        let Some((columns, rows)) = self.cell_span(area) else {
+            return;
+        };
+        for y in rows.clone() {
+            for x in columns.clone() {
+                let cell = self.cells[y * self.resolution.0 + x].clone();
+                for &index in &self.indices[cell.start as usize..cell.end as usize] {
+                    let shape = &self.shapes[index as usize];
+                    // Only visit the shape in the first cell it shares with the area.
+                    let (shape_columns, shape_rows) = self.cell_span(shape.bounds()).unwrap();
+                    if (
+                        shape_columns.start.max(columns.start),
+                        shape_rows.start.max(rows.start),
+                    ) == (x, y)
+                    {
+                        f(shape);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns twice the signed area of the polygon.
+fn signed_area(vertices: &[Vec2]) -> f32 {
+    (0..vertices.len())
+        .map(|i| vertices[i].perp_dot(vertices[(i + 1) % vertices.len()]))
+        .sum()
+}
+
+/// Returns how far the circle has to be moved to stop overlapping
+/// the convex polygon or `None` if they don't overlap.
+fn polygon_penetration(vertices: &[Vec2], center: Vec2, radius: f32) -> Option<Vec2> {
+    let winding = signed_area(vertices).signum();
+    let edges = || (0..vertices.len()).map(|i| (vertices[i], vertices[(i + 1) % vertices.len()]));
+    // The signed distance to the closest edge's line and its outward normal.
+    let (dist, normal) = edges()
+        .map(|(a, b)| {
+            let normal = (Vec2::new(b.y - a.y, a.x - b.x) * winding).normalize_or_zero();
+            ((center - a).dot(normal), normal)
+        })
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .unwrap();
+    if dist <= 0. {
+        // The center is inside, so push it out through the closest edge.
+        return Some(normal * (radius - dist));
+    }
+    let closest = edges()
+        .map(|(a, b)| {
+            let edge = b - a;
+            let t = ((center - a).dot(edge) / edge.length_squared()).clamp(0., 1.);
+            a + edge * t
+        })
+        .min_by(|a, b| {
+            a.distance_squared(center)
+                .total_cmp(&b.distance_squared(center))
+        })
+        .unwrap();
+    let dist = closest.distance(center);
+    (dist < radius).then(|| (center - closest) / dist * (radius - dist))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "Polygon must not be degenerate!")]
+    fn polygon_rejects_collinear_vertices() {
+        StaticShape::polygon([Vec2::ZERO, Vec2::new(1., 1.), Vec2::new(3., 3.)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Polygon must be convex!")]
+    fn polygon_rejects_concave_vertices() {
+        StaticShape::polygon([
+            Vec2::ZERO,
+            Vec2::new(4., 0.),
+            Vec2::new(1., 1.),
+            Vec2::new(0., 4.),
+        ]);
+    }
+
+    #[test]
+    fn tiles_merge_runs_within_rows() {
+        let rows = ["##.#", "....", ".###"]
+            .map(|row| row.chars().map(|tile| tile == '#').collect::<Vec<_>>());
+        let rects: Vec<_> = StaticShape::tiles(Vec2::new(10., 20.), 5., rows)
+            .map(|shape| shape.bounds())
+            .collect();
+        assert_eq!(
+            rects,
+            [
+                Rect::new(10., 20., 10., 5.),
+                Rect::new(25., 20., 5., 5.),
+                Rect::new(15., 30., 15., 5.),
+            ]
+        );
+    }
+
+    #[test]
+    fn push_out_moves_circles_out_of_walls() {
+        let geometry =
+            StaticGeometry::new(vec![StaticShape::Rect(Rect::new(0., 0., 100., 100.))], 50.);
+        let collider = Collider {
+            shape: Shape::Circle { radius: 10. },
+            monitorable: CollisionLayer::Enemy.mask(),
+            monitoring: CollisionLayer::Player.mask(),
+            body: None,
+        };
+        let position = geometry.push_out(&collider, Vec2::new(95., 50.));
+        assert!(position.distance(Vec2::new(110., 50.)) < 1e-4);
+        assert!(!geometry.blocks(&collider, position + Vec2::X * 1e-3));
+        assert!(geometry.blocks(&collider, Vec2::new(50., 50.)));
+    }
+}
@@ -0,0 +1,77 @@
+use macroquad::math::Rect;
+
+use crate::{
+    ecs::{component::ComponentPool, entity::Entity},
+    game::{
+        collision::{Collider, SpatialIndex},
+        transform::Transform,
+    },
+};
+
+/// Sort-and-sweep that allows to efficiently detect collisions
+/// for the [`Collider`] components.
+///
+/// The bounding boxes are sorted by their left edge, so the overlapping pairs
+/// can be found by sweeping a line from left to right. Unlike the spatial
+/// partitions, it does not depend on the size of the map at all.
+#[derive(Debug, Clone, Default)]
+pub struct SortAndSweep {
+    entries: Vec<(Entity, Rect)>,
+    /// The width of the widest bounding box.
+    width_max: f32,
+}
+
+impl SortAndSweep {
+    /// Constructs an empty sort-and-sweep.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl SpatialIndex for SortAndSweep {
+    fn update(
+        &mut self,
+        transforms: &ComponentPool<Transform>,
+        colliders: &ComponentPool<Collider>,
+        _bounding_rect: Rect,
+    ) {
+        self.entries.clear();
+        self.width_max = 0.;
+        for (entity, collider) in colliders.iter() {
+            let transform = transforms
+                .get(entity)
+                .expect("All colliders must have a Transform component!");
+            let bounds = collider.bounds(transform.position);
+            self.width_max = self.width_max.max(bounds.w);
+            self.entries.push((entity, bounds));
+        }
+        self.entries
+            .sort_unstable_by(|a, b| a.1.x.total_cmp(&b.1.x));
+    }
+    fn query_area(&self, area: Rect, out: &mut Vec<Entity>) {
+        // No bounding box starting before this point can reach the area.
+        let start = self
+            .entries
+            .partition_point(|(_, bounds)| bounds.x < area.x - self.width_max);
+        for &(entity, bounds) in &self.entries[start..] {
+            if bounds.x > area.right() {
+                break;
+            }
+            if bounds.overlaps(&area) {
+                out.push(entity);
+            }
+        }
+    }
+    fn candidate_pairs(&self, out: &mut Vec<(Entity, Entity)>) {
+        for (i, &(entity, bounds)) in self.entries.iter().enumerate() {
+            for &(other_entity, other_bounds) in &self.entries[i + 1..] {
+                if other_bounds.x > bounds.right() {
+                    break;
+                }
+                if bounds.overlaps(&other_bounds) {
+                    out.push((entity, other_entity));
+                }
+            }
+        }
+    }
+}
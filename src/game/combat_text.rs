@@ -0,0 +1,217 @@
+use macroquad::{
+    color::{Color, GREEN, RED, WHITE, YELLOW},
+    math::Vec2,
+};
+
+use crate::{
+    Context,
+    ecs::{
+        component::{Component, ComponentPool},
+        entity::{Entity, World},
+    },
+    game::{
+        rendering::draw_list::{DrawCommand, DrawList},
+        transform::Transform,
+    },
+};
+
+/// What the [`CombatText`] is reporting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CombatTextKind {
+    /// The damage dealt to an enemy.
+    Damage,
+    /// The critical damage dealt to an enemy.
+    Crit,
+    /// The health restored to the player.
+    Heal,
+    /// The damage taken by the player.
+    PlayerDamage,
+}
+
+impl CombatTextKind {
+    /// Returns the color and the font size of the text.
+    fn style(self) -> (Color, f32) {
+        match self {
+            Self::Damage => (WHITE, 24.),
+            Self::Crit => (YELLOW, 36.),
+            Self::Heal => (GREEN, 24.),
+            Self::PlayerDamage => (RED, 30.),
+        }
+    }
+}
+
+/// A component for the number floating up from the place where something was hit or healed.
+#[derive(Debug, Clone, Copy)]
+pub struct CombatText {
+    pub kind: CombatTextKind,
+    pub value: u32,
+    /// The time since the text was spawned in seconds.
+    pub age: f32,
+    /// `false` if the text has expired and waits to be reused.
+    pub is_active: bool,
+}
+
+impl Component for CombatText {}
+
+impl CombatText {
+    /// How long the text stays on the screen in seconds.
+    pub const LIFETIME: f32 = 0.8;
+    /// How fast the text rises.
+    const RISE_SPEED: f32 = 50.;
+
+    /// Returns the opacity of the text, which fades out over its lifetime.
+    fn alpha(&self) -> f32 {
+        (1. - self.age / Self::LIFETIME).clamp(0., 1.)
+    }
+}
+
+/// A resource that keeps the expired [`CombatText`] entities around for reuse,
+/// so that spawning lots of texts doesn't create and destroy lots of entities.
+#[derive(Debug, Clone, Default)]
+pub struct CombatTextPool {
+    free: Vec<Entity>,
+}
+
+impl CombatTextPool {
+    /// Constructs an empty pool.
+    pub fn new() -> Self {
+        Default::default()
+    }
+    /// Shows the text at the position, reusing an expired text entity if there is one.
+    pub fn spawn(
+        &mut self,
+        world: &mut World,
+        position: Vec2,
+        kind: CombatTextKind,
+        value: u32,
+    ) -> Entity {
+        let transform = Transform {
+            position,
+            rotation: 0.,
+        };
+        let text = CombatText {
+            kind,
+            value,
+            age: 0.,
+            is_active: true,
+        };
+        // The expired texts keep their components, so they are overwritten in place.
+        if let Some(entity) = self.free.pop() {
+            *world
+                .borrow_pool_mut::<Transform>()
+                .get_mut(entity)
+                .unwrap() = transform;
+            *world
+                .borrow_pool_mut::<CombatText>()
+                .get_mut(entity)
+                .unwrap() = text;
+            return entity;
+        }
+        let entity = world.create_entity();
+        world
+            .borrow_pool_mut::<Transform>()
+            .insert(entity, transform);
+        world.borrow_pool_mut::<CombatText>().insert(entity, text);
+        entity
+    }
+    /// Moves the active texts up and takes the expired ones back into the pool.
+    pub fn update(&mut self, world: &World, delta: f32) {
+        let mut texts = world.borrow_pool_mut::<CombatText>();
+        let mut transforms = world.borrow_pool_mut::<Transform>();
+        for (entity, text) in texts.iter_mut() {
+            if !text.is_active {
+                continue;
+            }
+            text.age += delta;
+            if text.age >= CombatText::LIFETIME {
+                text.is_active = false;
+                self.free.push(entity);
+                continue;
+            }
+            transforms.get_mut(entity).unwrap().position.y -= CombatText::RISE_SPEED * delta;
+        }
+    }
+}
+
+/// Moves the texts up and returns the expired ones to the [`CombatTextPool`].
+pub fn update_combat_texts(context: &mut Context, delta: f32) {
+    context.combat_texts.update(&context.world, delta);
+}
+
+/// Records the commands drawing the active texts on the layer
+/// centered horizontally with their baselines at their positions.
+pub fn record_combat_texts(
+    texts: &ComponentPool<CombatText>,
+    transforms: &ComponentPool<Transform>,
+    layer: u8,
+    draw_list: &mut DrawList,
+) {
+    for (entity, text) in texts.iter() {
+        if !text.is_active {
+            continue;
+        }
+        let position = transforms.get(entity).unwrap().position;
+        let (color, font_size) = text.kind.style();
+        let label = match text.kind {
+            CombatTextKind::Heal => format!("+{}", text.value),
+            CombatTextKind::Crit => format!("{}!", text.value),
+            CombatTextKind::Damage | CombatTextKind::PlayerDamage => text.value.to_string(),
+        };
+        draw_list.push(
+            layer,
+            DrawCommand::Text {
+                text: label,
+                position,
+                font_size,
+                align: Vec2::new(0.5, 1.),
+                color: Color::new(color.r, color.g, color.b, text.alpha()),
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expired_texts_are_reused() {
+        let mut world = World::new();
+        world.register_type::<Transform>();
+        world.register_type::<CombatText>();
+        let mut pool = CombatTextPool::new();
+        let entity = pool.spawn(&mut world, Vec2::ZERO, CombatTextKind::Damage, 1);
+        pool.update(&world, CombatText::LIFETIME / 2.);
+        assert!(
+            world
+                .borrow_pool::<CombatText>()
+                .get(entity)
+                .unwrap()
+                .is_active
+        );
+        pool.update(&world, CombatText::LIFETIME);
+        assert!(
+            !world
+                .borrow_pool::<CombatText>()
+                .get(entity)
+                .unwrap()
+                .is_active
+        );
+        let position = Vec2::new(10., 20.);
+        let reused = pool.spawn(&mut world, position, CombatTextKind::Heal, 2);
+        assert_eq!(reused, entity);
+        let texts = world.borrow_pool::<CombatText>();
+        let text = texts.get(reused).unwrap();
+        assert!(text.is_active);
+        assert_eq!(
+            (text.kind, text.value, text.age),
+            (CombatTextKind::Heal, 2, 0.)
+        );
+        let transforms = world.borrow_pool::<Transform>();
+        assert_eq!(transforms.get(reused).unwrap().position, position);
+        drop((texts, transforms));
+        // The pool is empty again, so the next text gets a new entity.
+        let other = pool.spawn(&mut world, position, CombatTextKind::Crit, 3);
+        assert_ne!(other, entity);
+    }
+}
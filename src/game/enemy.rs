@@ -1,28 +1,61 @@
-use macroquad::{math::Vec2, rand::gen_range, time::get_time};
+use macroquad::{color::Color, math::Vec2, rand::gen_range};
 
 use crate::{
     Context, FIXED_DELTA,
     ecs::{component::Component, entity::Entity},
     game::{
-        collision::{Collider, CollisionMask, Shape},
+        animation::{Animation, ClipKind},
+        collision::{Body, Collider, Shape, layer::CollisionLayer},
+        combat_text::CombatTextKind,
         hp::Hp,
+        particles::ParticleEffect,
         rendering::{Sprite, SpriteSource},
         transform::Transform,
+        trigger::Trigger,
     },
 };
 
+/// An enemy announced by a spawn marker.
 #[derive(Debug, Clone, Copy)]
+pub struct PendingSpawn {
+    pub position: Vec2,
+    /// The time the enemy spawns at.
+    pub time: f32,
+    pub is_elite: bool,
+}
+
+#[derive(Debug, Clone)]
 pub struct Director {
     pub next_batch_time: f32,
+    /// The number of batches spawned so far.
+    pub wave: u32,
+    /// The enemies of the batches that are about to spawn.
+    pub pending_spawns: Vec<PendingSpawn>,
 }
 
 impl Director {
+    /// How long the spawn markers are shown before the enemies spawn in seconds.
+    const SPAWN_WARNING: f32 = 1.;
+    /// Every batch with the number divisible by it starts with an elite.
+    const ELITE_WAVE_INTERVAL: u32 = 5;
+    /// How many random positions are tried for an enemy before it's left out of the batch.
+    const SPAWN_ATTEMPTS_MAX: usize = 32;
+
     pub fn spawn_enemies(&mut self, context: &mut Context) {
-        if self.next_batch_time >= get_time() as f32 {
+        let time = context.time;
+        self.pending_spawns.retain(|spawn| {
+            if spawn.time > time {
+                return true;
+            }
+            spawn_enemy(spawn.position, spawn.is_elite, context);
+            false
+        });
+        if self.next_batch_time >= time {
             return;
         }
         let level = context.player.exp.level();
-        self.next_batch_time = get_time() as f32 + 6. / (10. + level as f32).log10();
+        self.next_batch_time = time + 6. / (10. + level as f32).log10();
+        self.wave += 1;
         let batch_len = 3 + level * 2;
         let c = context.map_constraints;
         let w = c.max.x - c.min.x;
@@ -32,28 +65,52 @@ impl Director {
             let transforms = context.world.borrow_pool_mut::<Transform>();
             transforms.get(context.player.entity).unwrap().position
         };
-        for _ in 0..batch_len {
-            let pos = loop {
+        for index in 0..batch_len {
+            let found = (0..Self::SPAWN_ATTEMPTS_MAX).find_map(|_| {
                 let random_pos =
                     Vec2::new(gen_range(c.min.x, c.max.x), gen_range(c.min.y, c.max.y));
-                if pos.distance(random_pos) < min_dist {
-                    continue;
-                }
-                break random_pos;
+                let is_free = pos.distance(random_pos) >= min_dist
+                    && !context
+                        .static_geometry
+                        .blocks(&enemy_collider(), random_pos);
+                is_free.then_some(random_pos)
+            });
+            // Most of the map must be blocked, so skip the enemy rather than search forever.
+            let Some(pos) = found else {
+                continue;
             };
-            spawn_enemy(pos, context);
+            self.pending_spawns.push(PendingSpawn {
+                position: pos,
+                time: time + Self::SPAWN_WARNING,
+                is_elite: index == 0 && self.wave.is_multiple_of(Self::ELITE_WAVE_INTERVAL),
+            });
         }
     }
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct Enemy {
-    pub _padding: bool,
+    /// `true` for the tougher enemies pointed at by the off-screen indicators.
+    pub is_elite: bool,
 }
 
 impl Component for Enemy {}
 
-pub fn spawn_enemy(pos: Vec2, context: &mut Context) -> Entity {
+impl Enemy {
+    /// How many times more health the elites have.
+    const ELITE_HP_MULTIPLIER: u32 = 4;
+    /// The tint telling the elites apart.
+    const ELITE_TINT: Color = Color::new(1., 0.6, 0.6, 1.);
+}
+
+/// A component marking a defeated enemy that is left in place
+/// until its [`ClipKind::Death`] clip finishes.
+#[derive(Debug, Clone, Copy)]
+pub struct Corpse;
+
+impl Component for Corpse {}
+
+pub fn spawn_enemy(pos: Vec2, is_elite: bool, context: &mut Context) -> Entity {
     let entity = context.world.create_entity();
     let level = context.player.exp.level();
     let mut enemies = context.world.borrow_pool_mut::<Enemy>();
@@ -61,7 +118,8 @@ pub fn spawn_enemy(pos: Vec2, context: &mut Context) -> Entity {
     let mut colliders = context.world.borrow_pool_mut::<Collider>();
     let mut sprites = context.world.borrow_pool_mut::<Sprite>();
     let mut hps = context.world.borrow_pool_mut::<Hp>();
-    enemies.insert(entity, Enemy { _padding: true });
+    let mut animations = context.world.borrow_pool_mut::<Animation>();
+    enemies.insert(entity, Enemy { is_elite });
     transforms.insert(
         entity,
         Transform {
@@ -69,88 +127,148 @@ pub fn spawn_enemy(pos: Vec2, context: &mut Context) -> Entity {
             rotation: 0.,
         },
     );
-    colliders.insert(
-        entity,
-        Collider {
-            shape: Shape::Circle { radius: 20. },
-            monitorable: CollisionMask(0b10),
-            monitoring: CollisionMask(0b1),
-        },
-    );
-    sprites.insert(
-        entity,
-        Sprite {
-            texture: context.enemy_texture.weak_clone(),
-            source: SpriteSource::Collider,
-            is_visible: true,
-            layer: 1,
-        },
+    colliders.insert(entity, enemy_collider());
+    let mut sprite = Sprite::new(
+        context.enemy_texture,
+        SpriteSource::World(Vec2::splat(40.)),
+        1,
     );
+    let mut hp = 4 + level * 2;
+    if is_elite {
+        sprite.source = SpriteSource::World(Vec2::splat(60.));
+        sprite.tint = Enemy::ELITE_TINT;
+        hp *= Enemy::ELITE_HP_MULTIPLIER;
+    }
+    sprites.insert(entity, sprite);
     context.screen.add_sprite(entity, &sprites);
-    hps.insert(entity, Hp(4 + level * 2));
+    hps.insert(entity, Hp(hp));
+    let mut animation = context.enemy_animation.clone();
+    animation.play(ClipKind::Walk);
+    animations.insert(entity, animation);
     entity
 }
+fn enemy_collider() -> Collider {
+    Collider {
+        shape: Shape::Circle { radius: 20. },
+        monitorable: CollisionLayer::Enemy.mask(),
+        monitoring: CollisionLayer::Player.mask(),
+        body: Some(Body {
+            blocked_by: CollisionLayer::Enemy.mask(),
+            mass: 1.,
+            is_immovable: false,
+        }),
+    }
+}
 pub fn move_enemies(context: &Context) {
     const WALK_SPEED: f32 = 170.;
     let enemies = context.world.borrow_pool::<Enemy>();
     let mut transforms = context.world.borrow_pool_mut::<Transform>();
+    let colliders = context.world.borrow_pool::<Collider>();
+    let mut animations = context.world.borrow_pool_mut::<Animation>();
     let player_transform = transforms.get(context.player.entity).unwrap();
     let pos = player_transform.position;
-    for (entity, enemy) in enemies.iter() {
+    for entity in enemies.entities() {
         let transform = transforms.get_mut(entity).unwrap();
+        let collider = colliders.get(entity).unwrap();
         let delta = (pos - transform.position).normalize_or_zero() * WALK_SPEED * FIXED_DELTA;
-        transform.position.x += delta.x;
-        transform.position.y += delta.y;
+        transform.position = context
+            .static_geometry
+            .push_out(collider, transform.position + delta);
+        let animation = animations.get_mut(entity).unwrap();
+        if animation.is_interruptible() {
+            animation.play(ClipKind::Walk);
+        }
     }
 }
 pub fn enemy_contact_damage(context: &mut Context) {
     const PLAYER_INVUN_DURATION: f32 = 0.25;
     const CONTACT_DAMAGE: u32 = 1;
-    if context.player.invunerable_until >= get_time() as f32 {
+    const PLAYER_HIT_TRAUMA: f32 = 0.5;
+    if context.player.invunerable_until >= context.time {
         return;
     }
-    let enemies = context.world.borrow_pool::<Enemy>();
-    let transforms = context.world.borrow_pool_mut::<Transform>();
-    let colliders = context.world.borrow_pool_mut::<Collider>();
-    let mut has_collided = false;
-    for entity in context
-        .collisions
-        .collisions(&transforms, &colliders, context.player.entity)
+    let pos;
     {
-        if enemies.contains_entity(entity) {
-            has_collided = true;
-            break;
+        let enemies = context.world.borrow_pool::<Enemy>();
+        let transforms = context.world.borrow_pool::<Transform>();
+        let triggers = context.world.borrow_pool::<Trigger>();
+        let has_collided = triggers
+            .get(context.player.hurtbox)
+            .unwrap()
+            .overlapping()
+            .iter()
+            .any(|&entity| enemies.contains_entity(entity));
+        if !has_collided {
+            return;
         }
+        pos = transforms.get(context.player.entity).unwrap().position;
+        let mut hps = context.world.borrow_pool_mut::<Hp>();
+        let hp = hps.get_mut(context.player.entity).unwrap();
+        hp.0 = hp.0.saturating_sub(CONTACT_DAMAGE);
+        let mut animations = context.world.borrow_pool_mut::<Animation>();
+        animations
+            .get_mut(context.player.entity)
+            .unwrap()
+            .play(ClipKind::Hurt);
     }
-    if !has_collided {
-        return;
-    }
-    let mut hps = context.world.borrow_pool_mut::<Hp>();
-    let hp = hps.get_mut(context.player.entity).unwrap();
-    hp.0 = hp.0.saturating_sub(CONTACT_DAMAGE);
-    context.player.invunerable_until = get_time() as f32 + PLAYER_INVUN_DURATION;
+    context.player.invunerable_until = context.time + PLAYER_INVUN_DURATION;
+    context.camera.add_trauma(PLAYER_HIT_TRAUMA);
+    context.combat_texts.spawn(
+        &mut context.world,
+        pos,
+        CombatTextKind::PlayerDamage,
+        CONTACT_DAMAGE,
+    );
 }
+/// Turns the enemies without health into [`Corpse`]s playing the death clip
+/// and despawns the corpses whose clip has finished.
+///
+/// The corpses lose their [`Enemy`] and [`Collider`] components,
+/// so they are neither moved nor hit by anything.
 pub fn unalive_enemies(context: &mut Context) {
+    // Much smaller than the trauma of a hit, since the enemies die in crowds.
+    const KILL_TRAUMA: f32 = 0.1;
     let mut unalived = Vec::new();
+    let mut despawned = Vec::new();
     {
-        let enemies = context.world.borrow_pool::<Enemy>();
+        let mut enemies = context.world.borrow_pool_mut::<Enemy>();
+        let mut colliders = context.world.borrow_pool_mut::<Collider>();
+        let mut corpses = context.world.borrow_pool_mut::<Corpse>();
+        let mut animations = context.world.borrow_pool_mut::<Animation>();
         let sprites = context.world.borrow_pool::<Sprite>();
         let hps = context.world.borrow_pool::<Hp>();
+        let transforms = context.world.borrow_pool::<Transform>();
+        for entity in corpses.entities() {
+            if animations.get(entity).unwrap().is_finished() {
+                despawned.push(entity);
+                context.screen.remove_sprite(entity, &sprites);
+            }
+        }
         for entity in enemies.entities() {
             let hp = hps.get(entity).unwrap();
-            if hp.0 != 0 {
-                continue;
+            if hp.0 == 0 {
+                unalived.push(entity);
             }
-            unalived.push(entity);
-            context.screen.remove_sprite(entity, &sprites);
+        }
+        for &entity in &unalived {
+            enemies.remove(entity);
+            colliders.remove(entity);
+            corpses.insert(entity, Corpse);
+            animations.get_mut(entity).unwrap().play(ClipKind::Death);
+            let pos = transforms.get(entity).unwrap().position;
+            context
+                .particles
+                .emit(&ParticleEffect::DEATH, pos, -std::f32::consts::FRAC_PI_2);
+            context.camera.add_trauma(KILL_TRAUMA);
         }
     }
     let prev_level = context.player.exp.level();
     context.player.exp.0 += unalived.len() as u32;
+    context.player.materials += unalived.len() as u32;
     let new_level = context.player.exp.level();
     let _levelup = new_level - prev_level;
     {
         // TODO: player levelup..
     }
-    context.world.destroy_entities(&unalived);
+    context.world.destroy_entities(&despawned);
 }
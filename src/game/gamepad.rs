@@ -0,0 +1,76 @@
+/// A button of a gamepad in the standard layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    /// The bottom face button (e.g. A on the Xbox controllers).
+    South,
+    /// The right face button (e.g. B on the Xbox controllers).
+    East,
+    Start,
+    LeftShoulder,
+    RightShoulder,
+}
+
+/// A resource reading the buttons pressed on the connected gamepads.
+///
+/// Macroquad doesn't read the gamepads, so they are read with `gilrs`, which is only built
+/// with the `gamepad` feature and not for the web. Otherwise no button is ever pressed.
+#[derive(Default)]
+pub struct Gamepads {
+    /// The connection to the gamepads or `None` if they can't be read on the platform.
+    #[cfg(all(feature = "gamepad", not(target_arch = "wasm32")))]
+    gilrs: Option<gilrs::Gilrs>,
+    /// The buttons pressed since the update before the last one.
+    pressed: Vec<GamepadButton>,
+}
+
+impl Gamepads {
+    /// Connects to the gamepads.
+    pub fn new() -> Self {
+        Self {
+            #[cfg(all(feature = "gamepad", not(target_arch = "wasm32")))]
+            gilrs: gilrs::Gilrs::new().ok(),
+            pressed: Vec::new(),
+        }
+    }
+    /// Reads the buttons pressed on any of the gamepads since the last update.
+    pub fn update(&mut self) {
+        self.pressed.clear();
+        #[cfg(all(feature = "gamepad", not(target_arch = "wasm32")))]
+        if let Some(gilrs) = &mut self.gilrs {
+            while let Some(event) = gilrs.next_event() {
+                if let gilrs::EventType::ButtonPressed(button, _) = event.event
+                    && let Some(button) = GamepadButton::from_gilrs(button)
+                {
+                    self.pressed.push(button);
+                }
+            }
+        }
+    }
+    /// Returns `true` if the button was pressed on any of the gamepads before the last update.
+    pub fn is_pressed(&self, button: GamepadButton) -> bool {
+        self.pressed.contains(&button)
+    }
+}
+
+#[cfg(all(feature = "gamepad", not(target_arch = "wasm32")))]
+impl GamepadButton {
+    fn from_gilrs(button: gilrs::Button) -> Option<Self> {
+        use gilrs::Button;
+        Some(match button {
+            Button::DPadUp => Self::DPadUp,
+            Button::DPadDown => Self::DPadDown,
+            Button::DPadLeft => Self::DPadLeft,
+            Button::DPadRight => Self::DPadRight,
+            Button::South => Self::South,
+            Button::East => Self::East,
+            Button::Start => Self::Start,
+            Button::LeftTrigger => Self::LeftShoulder,
+            Button::RightTrigger => Self::RightShoulder,
+            _ => return None,
+        })
+    }
+}
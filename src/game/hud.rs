@@ -0,0 +1,312 @@
+use macroquad::{
+    color::{Color, GREEN, ORANGE, RED, SKYBLUE, WHITE, YELLOW},
+    math::{Rect, Vec2},
+};
+
+use crate::{
+    Context,
+    game::{
+        collision::{Collider, layer::CollisionLayer},
+        enemy::Enemy,
+        rendering::draw_list::{DrawCommand, DrawList},
+        transform::Transform,
+    },
+};
+
+/// What a [`Blip`] on the minimap stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlipKind {
+    Player,
+    Enemy,
+    /// An elite enemy, which is also pointed at from the edges of the screen when it's off-screen.
+    Elite,
+    Pickup,
+    /// A place where an enemy is about to spawn.
+    Spawn,
+}
+
+impl BlipKind {
+    fn color(self) -> Color {
+        match self {
+            Self::Player => WHITE,
+            Self::Enemy => RED,
+            Self::Elite => ORANGE,
+            Self::Pickup => GREEN,
+            Self::Spawn => YELLOW,
+        }
+    }
+}
+
+/// A point of interest shown on the minimap.
+#[derive(Debug, Clone, Copy)]
+pub struct Blip {
+    pub kind: BlipKind,
+    pub position: Vec2,
+}
+
+/// The values shown on the heads-up display.
+#[derive(Debug, Clone, Default)]
+pub struct Hud {
+    pub hp: u32,
+    pub max_hp: u32,
+    pub level: u32,
+    /// The experience gained since reaching the current level.
+    pub exp: u32,
+    /// The experience required to reach the next level.
+    pub exp_required: u32,
+    pub wave: u32,
+    /// The time left until the next wave in seconds.
+    pub wave_time_left: f32,
+    pub materials: u32,
+    /// The area of the world the minimap shows.
+    pub arena: Rect,
+    /// The area of the world visible on the screen.
+    pub view: Rect,
+    pub blips: Vec<Blip>,
+}
+
+impl Hud {
+    /// The distance from the edges of the screen relative to its height.
+    const MARGIN: f32 = 0.02;
+    /// The size of the bars relative to the size of the screen.
+    const BAR_SIZE: Vec2 = Vec2::new(0.3, 0.035);
+    /// The size of the font relative to the height of the screen.
+    const FONT_SIZE: f32 = 0.035;
+    const BAR_BACKGROUND: Color = Color::new(0., 0., 0., 0.6);
+    /// The size of the longer side of the minimap relative to the height of the screen.
+    const MINIMAP_SIZE: f32 = 0.25;
+    /// The size of the off-screen indicators relative to the height of the screen.
+    const INDICATOR_SIZE: f32 = 0.03;
+
+    /// Records the commands drawing the HUD on the layer in screen space
+    /// over the screen of the specified size.
+    ///
+    /// The HP and XP bars are drawn in the top left corner, the wave number and
+    /// the countdown at the top center and the materials in the top right corner.
+    pub fn record(&self, screen_size: Vec2, layer: u8, draw_list: &mut DrawList) {
+        let margin = screen_size.y * Self::MARGIN;
+        let bar_size = screen_size * Self::BAR_SIZE;
+        let font_size = screen_size.y * Self::FONT_SIZE;
+        let mut push = |command| draw_list.push(layer, command);
+
+        let hp_bar = Rect::new(margin, margin, bar_size.x, bar_size.y);
+        record_bar(
+            hp_bar,
+            ratio(self.hp, self.max_hp),
+            if self.hp * 4 <= self.max_hp {
+                RED
+            } else {
+                GREEN
+            },
+            format!("{} / {}", self.hp, self.max_hp),
+            font_size,
+            &mut push,
+        );
+        let exp_bar = Rect::new(
+            margin,
+            hp_bar.bottom() + margin / 2.,
+            bar_size.x,
+            bar_size.y,
+        );
+        record_bar(
+            exp_bar,
+            ratio(self.exp, self.exp_required),
+            SKYBLUE,
+            format!("LV.{}", self.level),
+            font_size,
+            &mut push,
+        );
+
+        let wave = format!("WAVE {}", self.wave);
+        let countdown = format!("{}", self.wave_time_left.max(0.).ceil());
+        push(text(
+            wave,
+            Vec2::new(screen_size.x / 2., margin),
+            font_size,
+            Vec2::new(0.5, 0.),
+        ));
+        push(text(
+            countdown,
+            Vec2::new(screen_size.x / 2., margin + font_size),
+            font_size,
+            Vec2::new(0.5, 0.),
+        ));
+        push(text(
+            format!("materials: {}", self.materials),
+            Vec2::new(screen_size.x - margin, margin),
+            font_size,
+            Vec2::new(1., 0.),
+        ));
+
+        self.record_minimap(screen_size, margin, &mut push);
+        self.record_indicators(screen_size, margin, &mut push);
+    }
+    /// Records the minimap in the bottom right corner.
+    fn record_minimap(&self, screen_size: Vec2, margin: f32, push: &mut impl FnMut(DrawCommand)) {
+        if self.arena.w <= 0. || self.arena.h <= 0. {
+            return;
+        }
+        let scale = screen_size.y * Self::MINIMAP_SIZE / self.arena.w.max(self.arena.h);
+        let size = self.arena.size() * scale;
+        let origin = screen_size - size - Vec2::splat(margin);
+        let to_minimap = |position: Vec2| origin + (position - self.arena.point()) * scale;
+        let rect = Rect::new(origin.x, origin.y, size.x, size.y);
+        push(DrawCommand::Rect {
+            rect,
+            color: Self::BAR_BACKGROUND,
+        });
+        push(DrawCommand::RectLines {
+            rect,
+            thickness: 2.,
+            color: WHITE,
+        });
+        let view_origin = to_minimap(
+            self.view
+                .point()
+                .clamp(self.arena.point(), self.arena.point() + self.arena.size()),
+        );
+        let view_end = to_minimap(
+            (self.view.point() + self.view.size())
+                .clamp(self.arena.point(), self.arena.point() + self.arena.size()),
+        );
+        let view_size = view_end - view_origin;
+        push(DrawCommand::RectLines {
+            rect: Rect::new(view_origin.x, view_origin.y, view_size.x, view_size.y),
+            thickness: 1.,
+            color: Color::new(1., 1., 1., 0.4),
+        });
+        let radius = size.max_element() * 0.015;
+        for blip in &self.blips {
+            let center = to_minimap(blip.position);
+            let color = blip.kind.color();
+            push(match blip.kind {
+                BlipKind::Spawn => DrawCommand::CircleLines {
+                    center,
+                    radius,
+                    thickness: 1.,
+                    color,
+                },
+                BlipKind::Player | BlipKind::Elite => DrawCommand::Circle {
+                    center,
+                    radius: radius * 1.5,
+                    color,
+                },
+                BlipKind::Enemy | BlipKind::Pickup => DrawCommand::Circle {
+                    center,
+                    radius,
+                    color,
+                },
+            });
+        }
+    }
+    /// Records the arrows at the edges of the screen pointing at the off-screen elites.
+    fn record_indicators(
+        &self,
+        screen_size: Vec2,
+        margin: f32,
+        push: &mut impl FnMut(DrawCommand),
+    ) {
+        if self.view.w <= 0. || self.view.h <= 0. {
+            return;
+        }
+        let size = screen_size.y * Self::INDICATOR_SIZE;
+        let center = screen_size / 2.;
+        let half_extent = center - Vec2::splat(margin + size);
+        for blip in &self.blips {
+            if blip.kind != BlipKind::Elite || self.view.contains(blip.position) {
+                continue;
+            }
+            let target = (blip.position - self.view.point()) / self.view.size() * screen_size;
+            let offset = target - center;
+            let direction = offset.normalize_or_zero();
+            // Push the arrow to the edge of the screen along the direction to the elite.
+            let scale = (half_extent / offset.abs()).min_element();
+            let tip = center + offset * scale;
+            let side = direction.perp() * size / 2.;
+            let base = tip - direction * size;
+            push(DrawCommand::Triangle {
+                vertices: [tip, base + side, base - side],
+                color: BlipKind::Elite.color(),
+            });
+        }
+    }
+}
+
+/// Returns the blips of the entities and the pending spawns to show on the minimap.
+pub fn minimap_blips(context: &Context) -> Vec<Blip> {
+    let transforms = context.world.borrow_pool::<Transform>();
+    let colliders = context.world.borrow_pool::<Collider>();
+    let enemies = context.world.borrow_pool::<Enemy>();
+    let mut res = Vec::new();
+    for (entity, collider) in colliders.iter() {
+        let kind = if let Some(enemy) = enemies.get(entity) {
+            if enemy.is_elite {
+                BlipKind::Elite
+            } else {
+                BlipKind::Enemy
+            }
+        } else if collider.monitorable.contains(CollisionLayer::Player) {
+            BlipKind::Player
+        } else if collider.monitorable.contains(CollisionLayer::Pickup) {
+            BlipKind::Pickup
+        } else {
+            continue;
+        };
+        res.push(Blip {
+            kind,
+            position: transforms.get(entity).unwrap().position,
+        });
+    }
+    if let Some(director) = &context.director {
+        res.extend(director.pending_spawns.iter().map(|spawn| Blip {
+            kind: BlipKind::Spawn,
+            position: spawn.position,
+        }));
+    }
+    res
+}
+
+/// Returns `value / max` clamped between `0` and `1`.
+fn ratio(value: u32, max: u32) -> f32 {
+    if max == 0 {
+        return 0.;
+    }
+    (value as f32 / max as f32).min(1.)
+}
+
+/// Records a bar filled by the specified fraction with the label centered on top of it.
+fn record_bar(
+    rect: Rect,
+    fill: f32,
+    color: Color,
+    label: String,
+    font_size: f32,
+    push: &mut impl FnMut(DrawCommand),
+) {
+    let border = rect.h * 0.1;
+    push(DrawCommand::Rect {
+        rect,
+        color: Hud::BAR_BACKGROUND,
+    });
+    push(DrawCommand::Rect {
+        rect: Rect::new(
+            rect.x + border,
+            rect.y + border,
+            (rect.w - border * 2.) * fill,
+            rect.h - border * 2.,
+        ),
+        color,
+    });
+    push(text(label, rect.center(), font_size, Vec2::splat(0.5)));
+}
+
+/// Returns the command drawing the white text aligned at the position.
+fn text(text: String, position: Vec2, font_size: f32, align: Vec2) -> DrawCommand {
+    DrawCommand::Text {
+        text,
+        position,
+        font_size,
+        align,
+        color: WHITE,
+    }
+}
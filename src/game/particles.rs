@@ -0,0 +1,328 @@
+use macroquad::{
+    color::Color,
+    math::{Rect, Vec2},
+    rand::gen_range,
+};
+
+use crate::game::rendering::draw_list::{DrawCommand, DrawList};
+
+/// The parameters of the particles spawned by an emitter.
+#[derive(Debug, Clone, Copy)]
+pub struct ParticleEffect {
+    /// The number of particles emitted at once when the emitter starts.
+    pub burst: u32,
+    /// The number of particles emitted every second after the burst.
+    pub rate: f32,
+    /// How long the emitter keeps emitting after the burst in seconds.
+    pub duration: f32,
+    /// The minimal and the maximal lifetime of a particle in seconds.
+    pub lifetime: (f32, f32),
+    /// The minimal and the maximal initial speed of a particle.
+    pub speed: (f32, f32),
+    /// The largest angle between the direction of the emitter
+    /// and the initial velocity of a particle in radians.
+    pub spread: f32,
+    /// The acceleration applied to every particle.
+    pub gravity: Vec2,
+    /// The color of a particle over its lifetime.
+    pub color: Curve<Color>,
+    /// The size of a particle over its lifetime.
+    pub size: Curve<f32>,
+}
+
+impl ParticleEffect {
+    /// Sparks flying off where a bullet hits something.
+    pub const HIT: Self = Self {
+        burst: 6,
+        rate: 0.,
+        duration: 0.,
+        lifetime: (0.15, 0.3),
+        speed: (80., 200.),
+        spread: 0.8,
+        gravity: Vec2::ZERO,
+        color: Curve::new(&[
+            (0., Color::new(1., 1., 0.9, 1.)),
+            (0.3, Color::new(1., 0.8, 0.3, 1.)),
+            (1., Color::new(1., 0.4, 0., 0.)),
+        ]),
+        size: Curve::new(&[(0., 5.), (1., 1.)]),
+    };
+    /// Debris scattering and falling down where an enemy dies.
+    pub const DEATH: Self = Self {
+        burst: 16,
+        rate: 40.,
+        duration: 0.15,
+        lifetime: (0.3, 0.6),
+        speed: (60., 180.),
+        spread: std::f32::consts::PI,
+        gravity: Vec2::new(0., 300.),
+        color: Curve::new(&[
+            (0., Color::new(0.9, 0.2, 0.2, 1.)),
+            (0.7, Color::new(0.5, 0.1, 0.1, 1.)),
+            (1., Color::new(0.3, 0.1, 0.1, 0.)),
+        ]),
+        // The debris swells a little before it shrinks.
+        size: Curve::new(&[(0., 6.), (0.2, 9.), (1., 3.)]),
+    };
+    /// A short flash in front of a weapon that shot.
+    pub const MUZZLE_FLASH: Self = Self {
+        burst: 4,
+        rate: 0.,
+        duration: 0.,
+        lifetime: (0.05, 0.1),
+        speed: (150., 300.),
+        spread: 0.3,
+        gravity: Vec2::ZERO,
+        color: Curve::new(&[
+            (0., Color::new(1., 1., 0.8, 1.)),
+            (1., Color::new(1., 0.7, 0.2, 0.)),
+        ]),
+        size: Curve::new(&[(0., 7.), (1., 2.)]),
+    };
+}
+
+/// A value that can be linearly interpolated.
+pub trait Lerp: Copy {
+    /// Returns the value the fraction `t` of the way from this value to the other one.
+    fn lerp(self, other: Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Lerp for Color {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        Color::new(
+            self.r.lerp(other.r, t),
+            self.g.lerp(other.g, t),
+            self.b.lerp(other.b, t),
+            self.a.lerp(other.a, t),
+        )
+    }
+}
+
+/// A value changing over the lifetime of a particle.
+///
+/// The curve is defined by the keys, each holding the value at some fraction of the lifetime.
+/// The value is interpolated linearly between the keys and held before the first
+/// and after the last one.
+#[derive(Debug, Clone, Copy)]
+pub struct Curve<T: 'static> {
+    keys: &'static [(f32, T)],
+}
+
+impl<T: Lerp> Curve<T> {
+    /// Constructs a curve from the keys sorted by their fractions of the lifetime.
+    ///
+    /// # Panics
+    /// Panics if there are no keys or the keys are not sorted.
+    pub const fn new(keys: &'static [(f32, T)]) -> Self {
+        assert!(!keys.is_empty(), "Curve must have keys!");
+        let mut index = 1;
+        while index < keys.len() {
+            assert!(
+                keys[index - 1].0 <= keys[index].0,
+                "Curve keys must be sorted!"
+            );
+            index += 1;
+        }
+        Self { keys }
+    }
+    /// Returns the value at the fraction `t` of the lifetime.
+    pub fn sample(&self, t: f32) -> T {
+        let next = self.keys.partition_point(|&(time, _)| time <= t);
+        match (self.keys.get(next.wrapping_sub(1)), self.keys.get(next)) {
+            (Some(&(time, value)), Some(&(next_time, next_value))) => {
+                value.lerp(next_value, (t - time) / (next_time - time))
+            }
+            (Some(&(_, value)), None) | (None, Some(&(_, value))) => value,
+            (None, None) => unreachable!(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Particle {
+    position: Vec2,
+    velocity: Vec2,
+    age: f32,
+    lifetime: f32,
+    gravity: Vec2,
+    color: Curve<Color>,
+    size: Curve<f32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Emitter {
+    effect: ParticleEffect,
+    position: Vec2,
+    direction: f32,
+    age: f32,
+    /// The fraction of a particle left over from the previous updates.
+    pending: f32,
+}
+
+/// A resource simulating the particles on the CPU.
+///
+/// The particles are kept outside of the [`World`](crate::ecs::entity::World) in plain vectors,
+/// since there are a lot of them and they never interact with anything. The number of live
+/// particles never exceeds the `budget`: the particles spawned past it are dropped.
+#[derive(Debug, Clone)]
+pub struct ParticleSystem {
+    particles: Vec<Particle>,
+    emitters: Vec<Emitter>,
+    budget: usize,
+    /// The sprite layer after which the particles are drawn.
+    pub layer: u8,
+}
+
+impl ParticleSystem {
+    /// Constructs an empty particle system drawn after the specified sprite layer.
+    pub fn new(budget: usize, layer: u8) -> Self {
+        Self {
+            particles: Vec::with_capacity(budget),
+            emitters: Vec::new(),
+            budget,
+            layer,
+        }
+    }
+    /// Returns the number of live particles.
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+    /// Returns `true` if there are no live particles.
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+    /// Starts the effect at the position with the particles flying in the `direction` (in radians).
+    pub fn emit(&mut self, effect: &ParticleEffect, position: Vec2, direction: f32) {
+        for _ in 0..effect.burst {
+            self.spawn(effect, position, direction);
+        }
+        if effect.rate > 0. && effect.duration > 0. {
+            self.emitters.push(Emitter {
+                effect: *effect,
+                position,
+                direction,
+                age: 0.,
+                pending: 0.,
+            });
+        }
+    }
+    /// Advances the emitters and the particles by `delta` seconds.
+    pub fn update(&mut self, delta: f32) {
+        let mut emitters = std::mem::take(&mut self.emitters);
+        emitters.retain_mut(|emitter| {
+            let time = delta.min(emitter.effect.duration - emitter.age);
+            emitter.age += delta;
+            emitter.pending += emitter.effect.rate * time;
+            while emitter.pending >= 1. {
+                emitter.pending -= 1.;
+                self.spawn(&emitter.effect, emitter.position, emitter.direction);
+            }
+            emitter.age < emitter.effect.duration
+        });
+        self.emitters = emitters;
+        let mut index = 0;
+        while index < self.particles.len() {
+            let particle = &mut self.particles[index];
+            particle.age += delta;
+            if particle.age >= particle.lifetime {
+                self.particles.swap_remove(index);
+                continue;
+            }
+            particle.velocity += particle.gravity * delta;
+            particle.position += particle.velocity * delta;
+            index += 1;
+        }
+    }
+    /// Records the commands drawing the particles as squares on the layer of the system.
+    pub fn record(&self, draw_list: &mut DrawList) {
+        for particle in &self.particles {
+            let t = particle.age / particle.lifetime;
+            let size = particle.size.sample(t);
+            let color = particle.color.sample(t);
+            let rect = Rect::new(
+                particle.position.x - size / 2.,
+                particle.position.y - size / 2.,
+                size,
+                size,
+            );
+            draw_list.push(self.layer, DrawCommand::Rect { rect, color });
+        }
+    }
+    fn spawn(&mut self, effect: &ParticleEffect, position: Vec2, direction: f32) {
+        if self.particles.len() >= self.budget {
+            return;
+        }
+        let angle = direction + gen_range(-effect.spread, effect.spread);
+        let speed = gen_range(effect.speed.0, effect.speed.1);
+        self.particles.push(Particle {
+            position,
+            velocity: Vec2::from_angle(angle) * speed,
+            age: 0.,
+            lifetime: gen_range(effect.lifetime.0, effect.lifetime.1),
+            gravity: effect.gravity,
+            color: effect.color,
+            size: effect.size,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curves_are_sampled_between_the_keys() {
+        let curve = Curve::new(&[(0.2, 10.), (0.5, 40.), (0.5, 0.), (1., 20.)]);
+        // The ends are held outside of the keys.
+        assert_eq!(curve.sample(0.), 10.);
+        assert_eq!(curve.sample(0.2), 10.);
+        assert_eq!(curve.sample(2.), 20.);
+        assert!((curve.sample(0.3) - 20.).abs() < 1e-4);
+        // The keys sharing a time make the value jump.
+        assert!((curve.sample(0.45) - 35.).abs() < 1e-4);
+        assert_eq!(curve.sample(0.5), 0.);
+        assert!((curve.sample(0.75) - 10.).abs() < 1e-4);
+        assert_eq!(Curve::new(&[(0.5, 3.)]).sample(0.1), 3.);
+    }
+
+    #[test]
+    fn color_curves_interpolate_every_channel() {
+        const CURVE: Curve<Color> = Curve::new(&[
+            (0., Color::new(1., 0., 0., 1.)),
+            (0.5, Color::new(0., 1., 0., 1.)),
+            (1., Color::new(0., 0., 1., 0.)),
+        ]);
+        assert_eq!(CURVE.sample(0.25), Color::new(0.5, 0.5, 0., 1.));
+        assert_eq!(CURVE.sample(0.75), Color::new(0., 0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn particles_follow_the_curves_of_their_effect() {
+        const EFFECT: ParticleEffect = ParticleEffect {
+            burst: 1,
+            rate: 0.,
+            duration: 0.,
+            lifetime: (1., 1.),
+            speed: (0., 0.),
+            spread: 0.,
+            gravity: Vec2::ZERO,
+            color: Curve::new(&[(0., Color::new(1., 1., 1., 1.))]),
+            size: Curve::new(&[(0., 2.), (0.5, 10.), (1., 0.)]),
+        };
+        let mut particles = ParticleSystem::new(1, 0);
+        particles.emit(&EFFECT, Vec2::ZERO, 0.);
+        particles.update(0.25);
+        let mut draw_list = DrawList::new();
+        particles.record(&mut draw_list);
+        let [(_, DrawCommand::Rect { rect, .. })] = draw_list.commands() else {
+            panic!("Particle must be drawn as a rectangle!");
+        };
+        assert_eq!(rect.size(), Vec2::splat(6.));
+    }
+}
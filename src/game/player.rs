@@ -1,13 +1,14 @@
-use macroquad::{math::Vec2, rand::gen_range, time::get_time};
+use macroquad::{math::Vec2, rand::gen_range};
 
 use crate::{
     Context,
     ecs::entity::Entity,
     game::{
+        animation::{Animation, ClipKind},
         bullet::Bullet,
-        collision::{Collider, CollisionMask, Shape},
-        enemy::Enemy,
+        collision::{Collider, Shape, layer::CollisionLayer},
         hp::Hp,
+        particles::ParticleEffect,
         player::{
             attributes::{Attr, Attrs},
             exp::Exp,
@@ -25,8 +26,12 @@ pub mod weapons;
 #[derive(Debug, Clone)]
 pub struct Player {
     pub entity: Entity,
+    /// The [`Trigger`](crate::game::trigger::Trigger) following the player
+    /// that detects the enemies touching them.
+    pub hurtbox: Entity,
     pub attrs: Attrs,
     pub exp: Exp,
+    pub materials: u32,
     pub weapons: Vec<Weapon>,
     pub invunerable_until: f32,
 }
@@ -86,12 +91,30 @@ impl Player {
 }
 pub fn player_move(context: &mut Context, delta: Vec2) {
     let mut transforms = context.world.borrow_pool_mut::<Transform>();
+    let colliders = context.world.borrow_pool::<Collider>();
     let player_pos;
     {
         let transform = transforms.get_mut(context.player.entity).unwrap();
+        let collider = colliders.get(context.player.entity).unwrap();
         let c = &context.map_constraints;
-        player_pos = (transform.position + delta).clamp(c.min, c.max);
+        player_pos = context
+            .static_geometry
+            .push_out(collider, transform.position + delta)
+            .clamp(c.min, c.max);
         transform.position = player_pos;
+        if delta.x != 0. {
+            let mut sprites = context.world.borrow_pool_mut::<Sprite>();
+            sprites.get_mut(context.player.entity).unwrap().flip_x = delta.x < 0.;
+        }
+        let mut animations = context.world.borrow_pool_mut::<Animation>();
+        let animation = animations.get_mut(context.player.entity).unwrap();
+        if animation.is_interruptible() {
+            animation.play(if delta == Vec2::ZERO {
+                ClipKind::Idle
+            } else {
+                ClipKind::Walk
+            });
+        }
     }
     for (index, weapon) in context.player.weapons.iter().enumerate() {
         let transform = transforms.get_mut(weapon.entity).unwrap();
@@ -102,41 +125,46 @@ pub fn player_move(context: &mut Context, delta: Vec2) {
 pub fn player_autoattack(context: &mut Context) {
     let evil_fucking_doppelganger_of_bad_code = context.player.clone();
     for weapon in context.player.weapons.iter_mut() {
-        if weapon.next_shot_time <= get_time() as f32 {
+        if weapon.next_shot_time <= context.time {
             // scout...
-            let mut closest: Option<Vec2> = None;
+            let closest: Option<Vec2>;
             let pos;
             {
-                let enemies = context.world.borrow_pool::<Enemy>();
                 let transforms = context.world.borrow_pool::<Transform>();
+                let colliders = context.world.borrow_pool::<Collider>();
                 pos = transforms.get(weapon.entity).unwrap().position;
-                for entity in enemies.entities() {
-                    let other_pos = transforms.get(entity).unwrap().position;
-                    if other_pos.distance(pos)
-                        > evil_fucking_doppelganger_of_bad_code.attack_range(weapon.base)
-                    {
-                        continue;
-                    }
-                    if closest.is_none_or(|closest| closest.distance(pos) < other_pos.distance(pos))
-                    {
-                        closest = Some(other_pos);
-                    }
-                }
+                closest = context
+                    .collisions
+                    .nearest(
+                        &transforms,
+                        &colliders,
+                        pos,
+                        CollisionLayer::Enemy.mask(),
+                        evil_fucking_doppelganger_of_bad_code.attack_range(weapon.base),
+                        1,
+                    )
+                    .first()
+                    .map(|&entity| transforms.get(entity).unwrap().position);
             }
             let Some(target) = closest else {
                 continue;
             };
             let direction = (target - pos).normalize_or(Vec2::Y);
             weapon.next_shot_time =
-                get_time() as f32 + evil_fucking_doppelganger_of_bad_code.attack_delay(weapon.base);
+                context.time + evil_fucking_doppelganger_of_bad_code.attack_delay(weapon.base);
+            context
+                .particles
+                .emit(&ParticleEffect::MUZZLE_FLASH, pos, direction.to_angle());
             let bullet = context.world.create_entity();
-            let damage = if gen_range(0., 1.)
-                <= evil_fucking_doppelganger_of_bad_code.crit_chance(weapon.base)
-            {
+            let is_crit =
+                gen_range(0., 1.) <= evil_fucking_doppelganger_of_bad_code.crit_chance(weapon.base);
+            let damage = if is_crit {
                 evil_fucking_doppelganger_of_bad_code.attack_crit_damage(weapon.base)
             } else {
                 evil_fucking_doppelganger_of_bad_code.attack_damage(weapon.base)
             };
+            let is_lifesteal = gen_range(0., 1.)
+                < evil_fucking_doppelganger_of_bad_code.lifesteal_chance(weapon.base);
             {
                 let mut transforms = context.world.borrow_pool_mut::<Transform>();
                 transforms.get_mut(weapon.entity).unwrap().rotation = direction.to_angle();
@@ -154,20 +182,27 @@ pub fn player_autoattack(context: &mut Context) {
                     bullet,
                     Collider {
                         shape: Shape::Circle { radius: 15. },
-                        monitorable: CollisionMask(0),
-                        monitoring: CollisionMask(0b10),
+                        monitorable: CollisionLayer::PlayerProjectile.mask(),
+                        monitoring: CollisionLayer::Enemy.mask(),
+                        body: None,
                     },
                 );
                 sprites.insert(
                     bullet,
-                    Sprite {
-                        texture: context.bullet_texture.weak_clone(),
-                        source: SpriteSource::Collider,
-                        is_visible: true,
-                        layer: 4,
+                    Sprite::new(
+                        context.bullet_texture,
+                        SpriteSource::World(Vec2::splat(30.)),
+                        4,
+                    ),
+                );
+                bullets.insert(
+                    bullet,
+                    Bullet {
+                        damage,
+                        is_crit,
+                        is_lifesteal,
                     },
                 );
-                bullets.insert(bullet, Bullet { damage });
                 context.screen.add_sprite(bullet, &sprites);
             }
         }
@@ -7,12 +7,17 @@ impl Component for Exp {}
 
 impl Exp {
     pub fn level(&self) -> u32 {
+        self.level_progress().0
+    }
+    /// Returns the level alongside the experience gained since reaching it
+    /// and the experience required to reach the next level.
+    pub fn level_progress(&self) -> (u32, u32, u32) {
         let mut total = 0;
         for level in 1.. {
             let req = u32::pow(level + 3, 2);
             total += req;
             if self.0 < total {
-                return level - 1;
+                return (level - 1, self.0 + req - total, req);
             }
         }
         unreachable!()
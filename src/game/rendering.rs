@@ -1,156 +1,1051 @@
 use crate::{
+    Context,
     ecs::{
         component::{Component, ComponentPool},
         entity::Entity,
     },
     game::{
-        collision::{Collider, Shape},
+        collision::{
+            Collider, Shape, SpatialIndex,
+            layer::{COLLISION_MATRIX, CollisionLayer},
+            statics::StaticShape,
+        },
+        combat_text::{CombatText, record_combat_texts},
+        hud::Hud,
+        particles::ParticleSystem,
+        tilemap::Tilemap,
         transform::Transform,
+        ui::UiCommand,
     },
 };
+use draw_list::{
+    DrawBackend, DrawCommand, DrawList, RenderStats, SortMode, SpriteLayers, TextureCommand,
+};
 use macroquad::{
     camera::{Camera2D, set_camera, set_default_camera},
-    color::{BLACK, Color, WHITE},
+    color::{BLACK, Color, GOLD, GRAY, GREEN, ORANGE, RED, SKYBLUE, VIOLET, WHITE},
+    material::{Material, MaterialParams, gl_use_default_material, gl_use_material, load_material},
     math::{Rect, Vec2},
-    text::draw_text,
-    texture::{DrawTextureParams, RenderTarget, Texture2D, draw_texture_ex, render_target},
+    miniquad::{BlendFactor, BlendState, BlendValue, Equation, PipelineParams, ShaderSource},
+    shapes::{
+        draw_circle, draw_circle_lines, draw_line, draw_rectangle, draw_rectangle_lines,
+        draw_triangle,
+    },
+    text::{draw_text, measure_text},
+    texture::{DrawTextureParams, FilterMode, Image, RenderTarget, draw_texture_ex, render_target},
     window::{clear_background, screen_height, screen_width},
 };
+use strum::{Display, EnumIter, EnumString, IntoEnumIterator};
+use textures::{TextureId, Textures};
+
+pub mod draw_list;
+pub mod textures;
+
+const FLASH_VERTEX_SHADER: &str = r#"#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+attribute vec4 color0;
+
+varying lowp vec2 uv;
+varying lowp vec4 color;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+    color = color0 / 255.0;
+    uv = texcoord;
+}"#;
+
+const FLASH_FRAGMENT_SHADER: &str = r#"#version 100
+varying lowp vec2 uv;
+varying lowp vec4 color;
+
+uniform sampler2D Texture;
+
+void main() {
+    gl_FragColor = vec4(color.rgb, color.a * texture2D(Texture, uv).a);
+}"#;
 
 #[derive(Debug, Clone, Copy)]
 pub enum SpriteSource {
-    Collider,
+    /// Placed at the [`Transform`] of the entity with the specified size,
+    /// which is independent from the size of its [`Collider`].
+    World(Vec2),
+    /// Placed into the rectangle regardless of the transform.
     Ui(Rect),
 }
 
 /// A component to render the texture of the entity.
 #[derive(Debug, Clone)]
 pub struct Sprite {
-    /// The texture of the sprite.
-    pub texture: Texture2D,
+    /// The texture of the sprite in the [`Textures`] of the [`Screen`].
+    pub texture: TextureId,
     pub source: SpriteSource,
+    /// The region of the texture to draw or `None` to draw the whole texture.
+    pub region: Option<Rect>,
     pub is_visible: bool,
     pub layer: u8,
+    /// The order of the sprite within its layer. The sprites with greater `z` are drawn on top.
+    pub z: i32,
+    /// The color multiplied with the texture, including the opacity.
+    pub tint: Color,
+    /// If `true`, the texture is mirrored horizontally.
+    pub flip_x: bool,
+    /// The point of the sprite placed at the position of the entity relative to the size
+    /// of the sprite, so that `(0.5, 0.5)` is the center. The sprite is rotated around it.
+    pub pivot: Vec2,
+    /// The time left until the sprite stops flashing white in seconds.
+    pub flash_time: f32,
 }
 
 impl Component for Sprite {}
 
+impl Sprite {
+    /// How long the sprite flashes white after [`Sprite::flash`] in seconds.
+    pub const FLASH_DURATION: f32 = 0.12;
+
+    /// Constructs a visible untinted sprite centered on the entity.
+    pub fn new(texture: TextureId, source: SpriteSource, layer: u8) -> Self {
+        Self {
+            texture,
+            source,
+            region: None,
+            is_visible: true,
+            layer,
+            z: 0,
+            tint: WHITE,
+            flip_x: false,
+            pivot: Vec2::splat(0.5),
+            flash_time: 0.,
+        }
+    }
+    /// Makes the sprite flash white for [`Sprite::FLASH_DURATION`].
+    pub fn flash(&mut self) {
+        self.flash_time = Self::FLASH_DURATION;
+    }
+}
+
+/// Counts down the flash timers of the sprites.
+pub fn update_sprite_flashes(context: &mut Context, delta: f32) {
+    let mut sprites = context.world.borrow_pool_mut::<Sprite>();
+    for sprite in sprites.values_mut() {
+        sprite.flash_time = (sprite.flash_time - delta).max(0.);
+    }
+}
+
+/// How the virtual screen is fitted into the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash, Display, EnumString, EnumIter)]
+#[strum(serialize_all = "kebab-case")]
+pub enum ScalingMode {
+    /// Scaled to fit the window keeping the aspect ratio with black bars along the sides.
+    #[default]
+    Letterbox,
+    /// Scaled by the largest whole factor that fits the window without filtering,
+    /// so that the pixels stay square and sharp.
+    PixelPerfect,
+    /// Stretched over the whole window ignoring the aspect ratio.
+    Stretch,
+    /// Scaled like [`ScalingMode::Letterbox`], but extended along one of the axes
+    /// to fill the window, showing more of the world instead of the bars.
+    Expand,
+}
+
+impl ScalingMode {
+    /// Returns the mode following this one, wrapping around after the last one.
+    pub fn next(self) -> Self {
+        Self::iter()
+            .cycle()
+            .skip_while(|&mode| mode != self)
+            .nth(1)
+            .unwrap()
+    }
+}
+
+/// Everything drawn in a frame by [`Screen::render_sprites`].
+pub struct Frame<'a> {
+    /// The point of the world at the center of the view.
+    pub target: Vec2,
+    pub zoom: Vec2,
+    /// The color the world is cleared with.
+    pub background: Color,
+    pub sprites: &'a ComponentPool<Sprite>,
+    pub transforms: &'a ComponentPool<Transform>,
+    pub tilemaps: &'a ComponentPool<Tilemap>,
+    pub colliders: &'a ComponentPool<Collider>,
+    /// The hand-placed shapes of the static level geometry.
+    ///
+    /// The solid tiles are left out, since the tiles draw themselves.
+    pub obstacles: &'a [StaticShape],
+    pub collisions: &'a dyn SpatialIndex,
+    pub combat_texts: &'a ComponentPool<CombatText>,
+    pub particles: &'a ParticleSystem,
+    pub hud: &'a Hud,
+    pub ui: &'a [UiCommand],
+}
+
 /// A virtual screen.
-#[derive(Debug, Clone)]
+///
+/// The frames are recorded into the [`DrawList`]s without touching the GPU,
+/// so the render target and the materials are only created once a frame is rendered.
+#[derive(Clone)]
 pub struct Screen {
-    vdisplay: RenderTarget,
-    layers: Box<[Vec<Entity>]>,
+    /// The texture the frames are rendered into or `None` until the next frame is rendered.
+    vdisplay: Option<RenderTarget>,
+    /// The virtual resolution in the landscape orientation.
+    resolution: (u32, u32),
+    scaling_mode: ScalingMode,
+    /// The area of the window the virtual screen is drawn into.
+    display: Rect,
+    /// The material drawing the textures as solid white silhouettes
+    /// or `None` until the first frame is rendered.
+    flash_material: Option<Material>,
+    textures: Textures,
+    sprite_layers: SpriteLayers,
+    /// The commands drawing the world during the last frame.
+    world_draw_list: DrawList,
+    /// The commands drawing over the world in screen space during the last frame.
+    screen_draw_list: DrawList,
+    /// The area of the world shown during the last frame.
+    view: Rect,
+    background: Color,
+    stats: RenderStats,
     vw: f32,
     vh: f32,
+    is_debug_overlay_visible: bool,
 }
 
 impl Screen {
-    /// Constructs a new virtual screen with the specified resolution.
+    /// The sprite layer after which the static level geometry is drawn.
+    const STATIC_GEOMETRY_LAYER: u8 = 0;
+    /// The color of the static level geometry.
+    const STATIC_GEOMETRY_COLOR: Color = Color::new(0.25, 0.2, 0.2, 1.);
+    /// The layer of the combat texts and the debug overlay drawn over all of the sprites.
+    const WORLD_OVERLAY_LAYER: u8 = u8::MAX;
+    /// The screen space layer of the HUD.
+    const HUD_LAYER: u8 = 0;
+    /// The screen space layer of the render stats shown with the debug overlay.
+    const STATS_LAYER: u8 = 1;
+    /// The screen space layer of the UI drawn over everything else.
+    const UI_LAYER: u8 = 2;
+
+    /// Constructs a new letterboxed virtual screen with the specified resolution.
     pub fn new(width: u32, height: u32) -> Self {
-        let vdisplay = render_target(width, height);
         let vw = width as f32;
         let vh = height as f32;
-        let layers = {
-            let mut res = Vec::new();
-            res.resize(u8::MAX as usize + 1, Vec::new());
-            res.into_boxed_slice()
-        };
+        let display = Rect::new(0., 0., vw, vh);
         Self {
-            vdisplay,
+            vdisplay: None,
+            resolution: (width, height),
+            scaling_mode: ScalingMode::default(),
+            display,
+            flash_material: None,
             vw,
             vh,
-            layers,
+            textures: Textures::new(),
+            sprite_layers: SpriteLayers::new(),
+            world_draw_list: DrawList::new(),
+            screen_draw_list: DrawList::new(),
+            view: display,
+            background: BLACK,
+            stats: RenderStats::default(),
+            is_debug_overlay_visible: false,
+        }
+    }
+    /// Packs the image into the texture atlas the sprites refer to it by.
+    pub fn add_texture(&mut self, image: &Image, filter: FilterMode) -> TextureId {
+        self.textures.add(image, filter)
+    }
+    /// Returns the size of the texture in the atlas.
+    pub fn texture_size(&self, id: TextureId) -> Vec2 {
+        self.textures.size(id)
+    }
+    /// Uploads the texture atlas to the GPU. Must be called after adding all of the textures.
+    pub fn build_textures(&mut self) {
+        self.textures.build();
+    }
+    /// Returns the resolution of the virtual screen.
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.vw, self.vh)
+    }
+    pub fn scaling_mode(&self) -> ScalingMode {
+        self.scaling_mode
+    }
+    pub fn set_scaling_mode(&mut self, mode: ScalingMode) {
+        self.scaling_mode = mode;
+        if let Some(vdisplay) = &self.vdisplay {
+            vdisplay.texture.set_filter(self.filter_mode());
         }
     }
+    /// Fits the virtual screen into the window according to the scaling mode.
+    ///
+    /// Must be called every frame before the rendering, since the window can be resized.
+    /// The virtual resolution is rotated in the portrait windows (e.g. in mobile browsers),
+    /// so that the game isn't squeezed into a thin strip in the middle of the screen.
+    pub fn update_layout(&mut self) {
+        let window = Vec2::new(screen_width(), screen_height());
+        if window.x <= 0. || window.y <= 0. {
+            return;
+        }
+        let (mut width, mut height) = self.resolution;
+        if (window.y > window.x) != (height > width) {
+            std::mem::swap(&mut width, &mut height);
+        }
+        let resolution = Vec2::new(width as f32, height as f32);
+        let fit = (window / resolution).min_element();
+        let (size, display_size) = match self.scaling_mode {
+            ScalingMode::Letterbox => (resolution, resolution * fit),
+            ScalingMode::PixelPerfect => {
+                // The window may be smaller than the virtual screen.
+                let scale = if fit >= 1. { fit.floor() } else { fit };
+                (resolution, resolution * scale)
+            }
+            ScalingMode::Stretch => (resolution, window),
+            ScalingMode::Expand => ((window / fit).round(), window),
+        };
+        self.display = Rect::new(
+            (window.x - display_size.x) / 2.,
+            (window.y - display_size.y) / 2.,
+            display_size.x,
+            display_size.y,
+        );
+        if size != self.size() {
+            // Recreated at the new size by the next frame.
+            self.vdisplay = None;
+            self.vw = size.x;
+            self.vh = size.y;
+        }
+    }
+    /// Reads the last rendered frame back from the GPU.
+    ///
+    /// # Panics
+    /// Panics if no frame was rendered since the virtual screen was resized.
+    pub fn read_pixels(&self) -> Image {
+        self.vdisplay
+            .as_ref()
+            .expect("Frame must be rendered before reading it back!")
+            .texture
+            .get_texture_data()
+    }
+    /// Converts the position in the window (e.g. of the mouse or a touch)
+    /// into the position on the virtual screen.
+    pub fn window_to_screen(&self, position: Vec2) -> Vec2 {
+        (position - self.display.point()) / self.display.size() * self.size()
+    }
+    fn filter_mode(&self) -> FilterMode {
+        match self.scaling_mode {
+            ScalingMode::PixelPerfect => FilterMode::Nearest,
+            _ => FilterMode::Linear,
+        }
+    }
+    /// Shows or hides the collision debug overlay.
+    pub fn toggle_debug_overlay(&mut self) {
+        self.is_debug_overlay_visible = !self.is_debug_overlay_visible;
+    }
+    /// Returns the number of the sprites drawn and culled during the last frame.
+    pub fn stats(&self) -> RenderStats {
+        self.stats
+    }
+    /// Sets the order the sprites on the layer are drawn in.
+    pub fn set_sort_mode(&mut self, layer: u8, mode: SortMode) {
+        self.sprite_layers.set_sort_mode(layer, mode);
+    }
     pub fn add_sprite(&mut self, entity: Entity, sprites: &ComponentPool<Sprite>) {
-        let sprite = sprites.get(entity).expect("Entity must have a sprite!");
-        self.layers[sprite.layer as usize].push(entity);
+        self.sprite_layers.add_sprite(entity, sprites);
     }
     pub fn remove_sprite(&mut self, entity: Entity, sprites: &ComponentPool<Sprite>) {
-        let sprite = sprites.get(entity).expect("Entity must have a sprite!");
-        let layer = &mut self.layers[sprite.layer as usize];
-        let index = layer
-            .iter()
-            .position(|&other| other == entity)
-            .expect("Entity was not added in the first place!");
-        layer.swap_remove(index);
-    }
-    #[allow(clippy::too_many_arguments)]
-    /// Renders the new frame onto the virtual screen and letterboxes the virtual screen onto the real screen.
-    pub fn render_sprites(
-        &self,
-        target: Vec2,
-        zoom: Vec2,
-        background: Color,
-        sprites: &ComponentPool<Sprite>,
-        transforms: &ComponentPool<Transform>,
-        colliders: &ComponentPool<Collider>,
-        level: u32,
-        hp: u32,
-    ) {
-        let cam = {
-            let mut res = Camera2D::from_display_rect(Rect::new(0., 0., self.vw, self.vh));
-            res.zoom *= zoom;
-            res.target = target;
-            res.render_target = Some(self.vdisplay.clone());
-            res
-        };
-        set_camera(&cam);
-        clear_background(background);
-        for layer in self.layers.iter() {
-            for &entity in layer.iter() {
-                let sprite = sprites.get(entity).unwrap();
-                if !sprite.is_visible {
-                    continue;
-                }
-                match sprite.source {
-                    SpriteSource::Collider => {
-                        let collider = colliders
-                            .get(entity)
-                            .expect("All entities with sprites must have colliders!");
-                        let transform = transforms.get(entity).unwrap();
-                        let Shape::Circle { radius } = collider.shape;
-                        draw_texture_ex(
-                            &sprite.texture,
-                            transform.position.x - radius,
-                            transform.position.y - radius,
-                            WHITE,
-                            DrawTextureParams {
-                                dest_size: Some(Vec2::splat(radius * 2.)),
-                                rotation: transform.rotation,
-                                ..Default::default()
-                            },
-                        );
-                    }
-                    SpriteSource::Ui(rect) => {
-                        draw_texture_ex(
-                            &sprite.texture,
-                            rect.point().x,
-                            rect.point().y,
-                            WHITE,
-                            DrawTextureParams {
-                                dest_size: Some(rect.size()),
-                                ..Default::default()
-                            },
-                        );
-                    }
-                }
+        self.sprite_layers.remove_sprite(entity, sprites);
+    }
+    /// Records the commands drawing the frame into the [`DrawList`]s without touching the GPU.
+    ///
+    /// The tiles, the sprites, the obstacles and the particles are drawn layer
+    /// by layer, culling the ones outside of the view, followed by the combat texts
+    /// and the debug overlay. The HUD, the render stats and then the UI are drawn
+    /// in screen space over the world.
+    pub fn record(&mut self, frame: &Frame) {
+        let Frame {
+            target,
+            zoom,
+            background,
+            sprites,
+            transforms,
+            tilemaps,
+            colliders,
+            obstacles,
+            collisions,
+            combat_texts,
+            particles,
+            hud,
+            ui,
+        } = *frame;
+        let view_size = self.size() / zoom;
+        let view = Rect::new(
+            target.x - view_size.x / 2.,
+            target.y - view_size.y / 2.,
+            view_size.x,
+            view_size.y,
+        );
+        self.view = view;
+        self.background = background;
+        let world = &mut self.world_draw_list;
+        world.clear();
+        for (entity, tilemap) in tilemaps.iter() {
+            let origin = transforms.get(entity).unwrap().position;
+            tilemap.record(origin, view, world);
+        }
+        self.stats = self.sprite_layers.record(view, sprites, transforms, world);
+        // The layers are sorted stably, so the commands recorded later
+        // are drawn after the tiles and the sprites on the same layer.
+        record_static_geometry(
+            obstacles,
+            Self::STATIC_GEOMETRY_COLOR,
+            Self::STATIC_GEOMETRY_LAYER,
+            world,
+        );
+        particles.record(world);
+        record_combat_texts(combat_texts, transforms, Self::WORLD_OVERLAY_LAYER, world);
+        let readout = self.is_debug_overlay_visible.then(|| {
+            record_debug_overlay(
+                transforms,
+                colliders,
+                collisions,
+                Self::WORLD_OVERLAY_LAYER,
+                world,
+            )
+        });
+        world.sort_by_layer();
+        self.stats.page_switches = world.page_switches(&self.textures);
+
+        let size = self.size();
+        let screen = &mut self.screen_draw_list;
+        screen.clear();
+        hud.record(size, Self::HUD_LAYER, screen);
+        if let Some(readout) = readout {
+            let stats = format!(
+                "sprites drawn: {}, culled: {}, page switches: {}",
+                self.stats.drawn, self.stats.culled, self.stats.page_switches
+            );
+            for (text, y) in [(readout, self.vh - 34.), (stats, self.vh - 10.)] {
+                screen.push(
+                    Self::STATS_LAYER,
+                    DrawCommand::Text {
+                        text,
+                        position: Vec2::new(10., y),
+                        font_size: 24.,
+                        align: Vec2::new(0., 1.),
+                        color: WHITE,
+                    },
+                );
             }
         }
-        draw_text(&format!("level: {level}\nhp: {hp}"), 0., 0., 32., WHITE);
-        let scale = f32::min(screen_width() / self.vw, screen_height() / self.vh);
+        for command in ui {
+            screen.push(Self::UI_LAYER, ui_draw_command(command));
+        }
+        screen.sort_by_layer();
+    }
+    /// Replays the recorded world and then the screen space commands with the backend.
+    pub fn replay(&self, backend: &mut impl DrawBackend) {
+        backend.begin_world(self.view, self.background);
+        self.world_draw_list.replay(backend);
+        backend.begin_screen();
+        self.screen_draw_list.replay(backend);
+    }
+    /// Renders the new frame onto the virtual screen and fits the virtual screen into the window.
+    ///
+    /// The frame is recorded by [`Screen::record`] and then replayed with macroquad.
+    pub fn render_sprites(&mut self, frame: &Frame) {
+        self.record(frame);
+        let filter = self.filter_mode();
+        let size = self.size();
+        let vdisplay = self
+            .vdisplay
+            .get_or_insert_with(|| {
+                let res = render_target(size.x as u32, size.y as u32);
+                res.texture.set_filter(filter);
+                res
+            })
+            .clone();
+        self.flash_material.get_or_insert_with(load_flash_material);
+        let mut backend = MacroquadBackend {
+            target: vdisplay.clone(),
+            size,
+            textures: &self.textures,
+            flash_material: self.flash_material.as_ref().unwrap(),
+        };
+        self.replay(&mut backend);
         set_default_camera();
         clear_background(BLACK);
         draw_texture_ex(
-            &cam.render_target.unwrap().texture,
-            (screen_width() - (self.vw * scale)) / 2.,
-            (screen_height() - (self.vh * scale)) / 2.,
+            &vdisplay.texture,
+            self.display.x,
+            self.display.y,
             WHITE,
             DrawTextureParams {
-                dest_size: Some(Vec2::new(self.vw * scale, self.vh * scale)),
+                dest_size: Some(self.display.size()),
                 flip_y: true,
                 ..Default::default()
             },
         );
     }
 }
+
+/// Compiles the material drawing the textures as solid white silhouettes.
+fn load_flash_material() -> Material {
+    load_material(
+        ShaderSource::Glsl {
+            vertex: FLASH_VERTEX_SHADER,
+            fragment: FLASH_FRAGMENT_SHADER,
+        },
+        MaterialParams {
+            pipeline_params: PipelineParams {
+                color_blend: Some(BlendState::new(
+                    Equation::Add,
+                    BlendFactor::Value(BlendValue::SourceAlpha),
+                    BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+                )),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )
+    .expect("Flash shader must compile!")
+}
+
+/// Returns the command drawing the UI element, whose text is positioned by its baseline.
+fn ui_draw_command(command: &UiCommand) -> DrawCommand {
+    match *command {
+        UiCommand::Rect { rect, color } => DrawCommand::Rect { rect, color },
+        UiCommand::Outline {
+            rect,
+            thickness,
+            color,
+        } => DrawCommand::RectLines {
+            rect,
+            thickness,
+            color,
+        },
+        UiCommand::Text {
+            ref text,
+            position,
+            font_size,
+            color,
+        } => DrawCommand::Text {
+            text: text.clone(),
+            position,
+            font_size,
+            align: Vec2::new(0., 1.),
+            color,
+        },
+        UiCommand::Texture { texture, dest } => DrawCommand::Texture(TextureCommand {
+            texture,
+            dest,
+            source: None,
+            rotation: 0.,
+            pivot: None,
+            flip_x: false,
+            tint: WHITE,
+            flash: 0.,
+        }),
+    }
+}
+
+/// Replays the [`DrawList`]s with macroquad onto the virtual screen.
+struct MacroquadBackend<'a> {
+    target: RenderTarget,
+    /// The resolution of the virtual screen.
+    size: Vec2,
+    textures: &'a Textures,
+    flash_material: &'a Material,
+}
+
+impl MacroquadBackend<'_> {
+    /// Draws the region of the texture, covering it
+    /// with a white silhouette while the sprite flashes.
+    fn draw_texture(&self, command: &TextureCommand) {
+        let (texture, region) = self.textures.get(command.texture);
+        let source = command.source.map_or(region, |source| {
+            Rect::new(region.x + source.x, region.y + source.y, source.w, source.h)
+        });
+        let params = DrawTextureParams {
+            dest_size: Some(command.dest.size()),
+            source: Some(source),
+            rotation: command.rotation,
+            flip_x: command.flip_x,
+            pivot: command.pivot,
+            ..Default::default()
+        };
+        let dest = command.dest;
+        draw_texture_ex(texture, dest.x, dest.y, command.tint, params.clone());
+        if command.flash > 0. {
+            gl_use_material(self.flash_material);
+            draw_texture_ex(
+                texture,
+                dest.x,
+                dest.y,
+                Color::new(1., 1., 1., command.flash),
+                params,
+            );
+            gl_use_default_material();
+        }
+    }
+}
+
+impl DrawBackend for MacroquadBackend<'_> {
+    fn begin_world(&mut self, view: Rect, background: Color) {
+        set_camera(&Camera2D {
+            render_target: Some(self.target.clone()),
+            ..Camera2D::from_display_rect(view)
+        });
+        clear_background(background);
+    }
+    fn begin_screen(&mut self) {
+        set_camera(&Camera2D {
+            render_target: Some(self.target.clone()),
+            ..Camera2D::from_display_rect(Rect::new(0., 0., self.size.x, self.size.y))
+        });
+    }
+    fn draw(&mut self, command: &DrawCommand) {
+        match command {
+            DrawCommand::Texture(command) => self.draw_texture(command),
+            &DrawCommand::Rect { rect, color } => {
+                draw_rectangle(rect.x, rect.y, rect.w, rect.h, color)
+            }
+            &DrawCommand::RectLines {
+                rect,
+                thickness,
+                color,
+            } => draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, thickness, color),
+            &DrawCommand::Circle {
+                center,
+                radius,
+                color,
+            } => draw_circle(center.x, center.y, radius, color),
+            &DrawCommand::CircleLines {
+                center,
+                radius,
+                thickness,
+                color,
+            } => draw_circle_lines(center.x, center.y, radius, thickness, color),
+            &DrawCommand::Line {
+                start,
+                end,
+                thickness,
+                color,
+            } => draw_line(start.x, start.y, end.x, end.y, thickness, color),
+            &DrawCommand::Triangle {
+                vertices: [a, b, c],
+                color,
+            } => draw_triangle(a, b, c, color),
+            DrawCommand::Text {
+                text,
+                position,
+                font_size,
+                align,
+                color,
+            } => {
+                let dimensions = measure_text(text, None, *font_size as u16, 1.);
+                draw_text(
+                    text,
+                    position.x - dimensions.width * align.x,
+                    position.y + dimensions.offset_y * (1. - align.y),
+                    *font_size,
+                    *color,
+                );
+            }
+        }
+    }
+}
+
+/// Records the shapes of the static level geometry filled with the color on the layer.
+fn record_static_geometry(
+    shapes: &[StaticShape],
+    color: Color,
+    layer: u8,
+    draw_list: &mut DrawList,
+) {
+    for shape in shapes {
+        match shape {
+            &StaticShape::Rect(rect) => draw_list.push(layer, DrawCommand::Rect { rect, color }),
+            StaticShape::Polygon(vertices) => {
+                for i in 1..vertices.len() - 1 {
+                    draw_list.push(
+                        layer,
+                        DrawCommand::Triangle {
+                            vertices: [vertices[0], vertices[i], vertices[i + 1]],
+                            color,
+                        },
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Records the cells of the collision index with their occupancy, the outlines of the colliders
+/// coloured by layer and the normals of the contacts between the colliders on the layer.
+///
+/// Returns the readout of the total number of cell entries and the busiest cell,
+/// which is drawn in screen space with the rest of the stats.
+fn record_debug_overlay(
+    transforms: &ComponentPool<Transform>,
+    colliders: &ComponentPool<Collider>,
+    collisions: &dyn SpatialIndex,
+    layer: u8,
+    draw_list: &mut DrawList,
+) -> String {
+    const CELL_COLOR: Color = Color::new(1., 1., 1., 0.25);
+    const NORMAL_LENGTH: f32 = 16.;
+    let mut cells = Vec::new();
+    collisions.debug_cells(&mut cells);
+    for &(rect, count) in &cells {
+        draw_list.push(
+            layer,
+            DrawCommand::RectLines {
+                rect,
+                thickness: 1.,
+                color: CELL_COLOR,
+            },
+        );
+        if count != 0 {
+            draw_list.push(
+                layer,
+                DrawCommand::Text {
+                    text: count.to_string(),
+                    position: Vec2::new(rect.x + 2., rect.y + 12.),
+                    font_size: 16.,
+                    align: Vec2::new(0., 1.),
+                    color: WHITE,
+                },
+            );
+        }
+    }
+    for (entity, collider) in colliders.iter() {
+        let position = transforms.get(entity).unwrap().position;
+        let Shape::Circle { radius } = collider.shape;
+        let color = collider
+            .monitorable
+            .layers()
+            .next()
+            .map_or(GRAY, layer_color);
+        draw_list.push(
+            layer,
+            DrawCommand::CircleLines {
+                center: position,
+                radius,
+                thickness: 1.,
+                color,
+            },
+        );
+    }
+    let mut pairs = Vec::new();
+    collisions.candidate_pairs(&mut pairs);
+    for (entity, other_entity) in pairs {
+        let (Some(collider), Some(other_collider)) =
+            (colliders.get(entity), colliders.get(other_entity))
+        else {
+            continue;
+        };
+        if !COLLISION_MATRIX.allows(collider.monitorable, other_collider.monitorable) {
+            continue;
+        }
+        let position = transforms.get(entity).unwrap().position;
+        let other_position = transforms.get(other_entity).unwrap().position;
+        let (
+            Shape::Circle { radius },
+            Shape::Circle {
+                radius: other_radius,
+            },
+        ) = (collider.shape, other_collider.shape);
+        let delta = other_position - position;
+        let overlap = radius + other_radius - delta.length();
+        if overlap <= 0. {
+            continue;
+        }
+        let normal = delta.normalize_or(Vec2::X);
+        let contact = position + normal * (radius - overlap / 2.);
+        let end = contact + normal * NORMAL_LENGTH;
+        draw_list.push(
+            layer,
+            DrawCommand::Line {
+                start: contact,
+                end,
+                thickness: 2.,
+                color: RED,
+            },
+        );
+    }
+    let total: usize = cells.iter().map(|&(_, count)| count).sum();
+    match cells.iter().max_by_key(|&&(_, count)| count) {
+        Some(&(rect, count)) => format!(
+            "cell entries: {total}, busiest cell: {count} at ({:.0}, {:.0})",
+            rect.x, rect.y
+        ),
+        None => format!("cell entries: {total}"),
+    }
+}
+
+/// Returns the color the colliders on the layer are drawn with in the debug overlay.
+fn layer_color(layer: CollisionLayer) -> Color {
+    match layer {
+        CollisionLayer::Player => GREEN,
+        CollisionLayer::Enemy => RED,
+        CollisionLayer::PlayerProjectile => SKYBLUE,
+        CollisionLayer::EnemyProjectile => ORANGE,
+        CollisionLayer::Pickup => GOLD,
+        CollisionLayer::Wall => GRAY,
+        CollisionLayer::Trigger => VIOLET,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ecs::entity::World,
+        game::{
+            collision::{Broadphase, layer::CollisionMask},
+            combat_text::{CombatTextKind, CombatTextPool},
+            particles::ParticleEffect,
+        },
+    };
+
+    /// Records the kinds of the replayed commands instead of drawing them.
+    #[derive(Default)]
+    struct RecordingBackend {
+        log: Vec<String>,
+    }
+
+    impl DrawBackend for RecordingBackend {
+        fn begin_world(&mut self, view: Rect, _background: Color) {
+            self.log
+                .push(format!("world {} {} {} {}", view.x, view.y, view.w, view.h));
+        }
+        fn begin_screen(&mut self) {
+            self.log.push("screen".to_owned());
+        }
+        fn draw(&mut self, command: &DrawCommand) {
+            self.log.push(match command {
+                DrawCommand::Texture(command) => format!("texture {}", command.texture.0),
+                DrawCommand::Rect { .. } => "rect".to_owned(),
+                DrawCommand::RectLines { .. } => "rect lines".to_owned(),
+                DrawCommand::Circle { .. } => "circle".to_owned(),
+                DrawCommand::CircleLines { .. } => "circle lines".to_owned(),
+                DrawCommand::Line { .. } => "line".to_owned(),
+                DrawCommand::Triangle { .. } => "triangle".to_owned(),
+                DrawCommand::Text { text, .. } => format!("text {text}"),
+            });
+        }
+    }
+
+    fn spawn_sprite(world: &mut World, screen: &mut Screen, sprite: Sprite, position: Vec2) {
+        let entity = world.create_entity();
+        world.borrow_pool_mut::<Transform>().insert(
+            entity,
+            Transform {
+                position,
+                rotation: 0.,
+            },
+        );
+        let mut sprites = world.borrow_pool_mut::<Sprite>();
+        sprites.insert(entity, sprite);
+        screen.add_sprite(entity, &sprites);
+    }
+
+    #[test]
+    fn frame_is_replayed_layer_by_layer() {
+        let mut screen = Screen::new(320, 180);
+        let image = Image::gen_image_color(8, 2, WHITE);
+        let tileset = screen.add_texture(&image, FilterMode::Nearest);
+        let texture = screen.add_texture(&image, FilterMode::Linear);
+        let mut world = World::new();
+        world.register_type::<Transform>();
+        world.register_type::<Sprite>();
+        world.register_type::<Tilemap>();
+        world.register_type::<Collider>();
+        world.register_type::<CombatText>();
+
+        let map = world.create_entity();
+        world.borrow_pool_mut::<Transform>().insert(
+            map,
+            Transform {
+                position: Vec2::splat(-100.),
+                rotation: 0.,
+            },
+        );
+        world.borrow_pool_mut::<Tilemap>().insert(
+            map,
+            Tilemap::parse(
+                "tileset 4 1\ntile_size 100\nlayer\n0 1\n",
+                tileset,
+                screen.texture_size(tileset),
+                0,
+            ),
+        );
+        let source = SpriteSource::World(Vec2::splat(20.));
+        spawn_sprite(
+            &mut world,
+            &mut screen,
+            Sprite::new(texture, source, 1),
+            Vec2::ZERO,
+        );
+        spawn_sprite(
+            &mut world,
+            &mut screen,
+            Sprite::new(texture, source, 0),
+            Vec2::X,
+        );
+        // Outside of the view, so it's culled.
+        spawn_sprite(
+            &mut world,
+            &mut screen,
+            Sprite::new(texture, source, 1),
+            Vec2::new(1000., 0.),
+        );
+        world.borrow_pool_mut::<Collider>().insert(
+            map,
+            Collider {
+                shape: Shape::Circle { radius: 10. },
+                monitoring: CollisionMask::NONE,
+                monitorable: CollisionLayer::Wall.mask(),
+                body: None,
+            },
+        );
+        let mut collisions = Broadphase::Grid.build();
+        collisions.update(
+            &world.borrow_pool::<Transform>(),
+            &world.borrow_pool::<Collider>(),
+            Rect::new(-200., -200., 400., 400.),
+        );
+        let mut particles = ParticleSystem::new(ParticleEffect::HIT.burst as usize, 1);
+        particles.emit(&ParticleEffect::HIT, Vec2::ZERO, 0.);
+        CombatTextPool::new().spawn(&mut world, Vec2::ZERO, CombatTextKind::Damage, 5);
+        screen.toggle_debug_overlay();
+
+        screen.record(&Frame {
+            target: Vec2::ZERO,
+            zoom: Vec2::ONE,
+            background: BLACK,
+            sprites: &world.borrow_pool::<Sprite>(),
+            transforms: &world.borrow_pool::<Transform>(),
+            tilemaps: &world.borrow_pool::<Tilemap>(),
+            colliders: &world.borrow_pool::<Collider>(),
+            obstacles: &[StaticShape::Rect(Rect::new(0., 0., 10., 10.))],
+            collisions: collisions.as_ref(),
+            combat_texts: &world.borrow_pool::<CombatText>(),
+            particles: &particles,
+            hud: &Hud::default(),
+            ui: &[UiCommand::Rect {
+                rect: Rect::new(0., 0., 320., 180.),
+                color: BLACK,
+            }],
+        });
+        let mut backend = RecordingBackend::default();
+        screen.replay(&mut backend);
+        let expected = [
+            "world -160 -90 320 180",
+            // Layer 0: the tiles, the sprites and the obstacles.
+            "texture 0",
+            "texture 0",
+            "texture 1",
+            "rect",
+            // Layer 1: the sprites and the particles.
+            "texture 1",
+            "rect",
+            "rect",
+            "rect",
+            "rect",
+            "rect",
+            "rect",
+            // The combat texts and the debug overlay over everything.
+            "text 5",
+            "rect lines",
+            "text 1",
+            "circle lines",
+            "screen",
+            // The HUD.
+            "rect",
+            "rect",
+            "text 0 / 0",
+            "rect",
+            "rect",
+            "text LV.0",
+            "text WAVE 0",
+            "text 0",
+            "text materials: 0",
+            // The debug stats.
+            "text cell entries: 1, busiest cell: 1 at (-200, -200)",
+            "text sprites drawn: 2, culled: 1, page switches: 2",
+            // The UI.
+            "rect",
+        ];
+        assert_eq!(backend.log, expected);
+    }
+
+    #[test]
+    fn solid_tiles_are_not_covered_by_the_obstacles() {
+        let mut screen = Screen::new(320, 180);
+        let image = Image::gen_image_color(8, 2, WHITE);
+        let tileset = screen.add_texture(&image, FilterMode::Nearest);
+        let mut world = World::new();
+        world.register_type::<Transform>();
+        world.register_type::<Sprite>();
+        world.register_type::<Tilemap>();
+        world.register_type::<Collider>();
+        world.register_type::<CombatText>();
+        let origin = Vec2::splat(-100.);
+        let tilemap = Tilemap::parse(
+            "tileset 4 1\nsolid 1\ntile_size 100\nlayer\n0 1\n1 0\n",
+            tileset,
+            screen.texture_size(tileset),
+            0,
+        );
+        let solid_tiles = tilemap.solid_shapes(origin);
+        assert_eq!(solid_tiles.len(), 2);
+        let map = world.create_entity();
+        world.borrow_pool_mut::<Transform>().insert(
+            map,
+            Transform {
+                position: origin,
+                rotation: 0.,
+            },
+        );
+        world.borrow_pool_mut::<Tilemap>().insert(map, tilemap);
+        let obstacle = Rect::new(120., -80., 20., 20.);
+
+        screen.record(&Frame {
+            target: Vec2::ZERO,
+            zoom: Vec2::ONE,
+            background: BLACK,
+            sprites: &world.borrow_pool::<Sprite>(),
+            transforms: &world.borrow_pool::<Transform>(),
+            tilemaps: &world.borrow_pool::<Tilemap>(),
+            colliders: &world.borrow_pool::<Collider>(),
+            obstacles: &[StaticShape::Rect(obstacle)],
+            collisions: Broadphase::Grid.build().as_ref(),
+            combat_texts: &world.borrow_pool::<CombatText>(),
+            particles: &ParticleSystem::new(0, 1),
+            hud: &Hud::default(),
+            ui: &[],
+        });
+        let rects: Vec<Rect> = screen
+            .world_draw_list
+            .commands()
+            .iter()
+            .filter_map(|(_, command)| match command {
+                &DrawCommand::Rect { rect, .. } => Some(rect),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(rects, [obstacle]);
+        for shape in &solid_tiles {
+            let &StaticShape::Rect(tile) = shape else {
+                panic!("Solid tiles must be rectangles!");
+            };
+            assert!(
+                !rects.iter().any(|rect| rect.overlaps(&tile)),
+                "Solid tile must not be covered by a flat rectangle!"
+            );
+        }
+    }
+}
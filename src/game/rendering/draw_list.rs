@@ -0,0 +1,316 @@
+use macroquad::{
+    color::Color,
+    math::{Rect, Vec2},
+};
+
+use crate::{
+    ecs::{component::ComponentPool, entity::Entity},
+    game::{
+        rendering::{
+            Sprite, SpriteSource,
+            textures::{TextureId, Textures},
+        },
+        transform::Transform,
+    },
+};
+
+/// The order the sprites are drawn in within a layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SortMode {
+    /// In the order the sprites were added.
+    Insertion,
+    /// By [`Sprite::z`], keeping the order of the sprites with the same `z`.
+    #[default]
+    Z,
+    /// By the bottom edge of the sprite, so that the sprites lower on the screen are drawn on top.
+    Y,
+}
+
+/// The number of the sprites drawn and culled during the last frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RenderStats {
+    pub drawn: usize,
+    /// The visible sprites skipped because they were outside of the view.
+    pub culled: usize,
+    /// The number of times a texture was drawn from a different atlas page
+    /// than the one before it or with the flash material.
+    ///
+    /// Each switch ends the batch macroquad merges the draws into, but the other shapes
+    /// drawn in between break the batches as well, so this is a lower bound of the draw calls.
+    pub page_switches: usize,
+}
+
+/// A command to draw a region of a texture.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureCommand {
+    pub texture: TextureId,
+    /// The rectangle the texture is drawn into before the rotation.
+    pub dest: Rect,
+    /// The region of the texture to draw or `None` to draw the whole texture.
+    pub source: Option<Rect>,
+    pub rotation: f32,
+    /// The point the texture is rotated around or `None` to rotate it around the center of `dest`.
+    pub pivot: Option<Vec2>,
+    pub flip_x: bool,
+    pub tint: Color,
+    /// The opacity of the white silhouette drawn over the texture.
+    pub flash: f32,
+}
+
+/// A command recorded into the [`DrawList`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawCommand {
+    Texture(TextureCommand),
+    Rect {
+        rect: Rect,
+        color: Color,
+    },
+    RectLines {
+        rect: Rect,
+        thickness: f32,
+        color: Color,
+    },
+    Circle {
+        center: Vec2,
+        radius: f32,
+        color: Color,
+    },
+    CircleLines {
+        center: Vec2,
+        radius: f32,
+        thickness: f32,
+        color: Color,
+    },
+    Line {
+        start: Vec2,
+        end: Vec2,
+        thickness: f32,
+        color: Color,
+    },
+    Triangle {
+        vertices: [Vec2; 3],
+        color: Color,
+    },
+    Text {
+        text: String,
+        position: Vec2,
+        font_size: f32,
+        /// The point of the text placed at `position` relative to the box between
+        /// its top and its baseline, so that `(0.5, 0.5)` is the center.
+        align: Vec2,
+        color: Color,
+    },
+}
+
+/// The target the [`DrawList`]s are replayed onto.
+///
+/// The game draws the commands with macroquad, while the tests
+/// record them to check what is drawn and in which order without a GPU.
+pub trait DrawBackend {
+    /// Starts drawing the area of the world inside of the `view`
+    /// over the screen cleared with the `background`.
+    fn begin_world(&mut self, view: Rect, background: Color);
+    /// Starts drawing in screen space over the world.
+    fn begin_screen(&mut self);
+    fn draw(&mut self, command: &DrawCommand);
+}
+
+/// The commands drawing a frame alongside the layers they are drawn on.
+///
+/// The list doesn't touch the GPU, so it can be recorded and inspected without one.
+#[derive(Debug, Clone, Default)]
+pub struct DrawList {
+    commands: Vec<(u8, DrawCommand)>,
+}
+
+impl DrawList {
+    /// Constructs an empty list.
+    pub fn new() -> Self {
+        Default::default()
+    }
+    pub fn commands(&self) -> &[(u8, DrawCommand)] {
+        &self.commands
+    }
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+    pub fn push(&mut self, layer: u8, command: DrawCommand) {
+        self.commands.push((layer, command));
+    }
+    pub fn clear(&mut self) {
+        self.commands.clear();
+    }
+    /// Sorts the commands by their layer keeping the order within the layers.
+    pub fn sort_by_layer(&mut self) {
+        self.commands.sort_by_key(|&(layer, _)| layer);
+    }
+    /// Returns the number of times the texture commands switch between the atlas pages.
+    pub fn page_switches(&self, textures: &Textures) -> usize {
+        let mut res = 0;
+        let mut last_page = None;
+        for (_, command) in &self.commands {
+            let DrawCommand::Texture(command) = command else {
+                continue;
+            };
+            let page = textures.page(command.texture);
+            // The flash material breaks the batch.
+            if last_page != Some(page) || command.flash > 0. {
+                res += 1;
+            }
+            last_page = (command.flash <= 0.).then_some(page);
+        }
+        res
+    }
+    /// Draws the commands with the backend in order.
+    pub fn replay(&self, backend: &mut impl DrawBackend) {
+        for (_, command) in &self.commands {
+            backend.draw(command);
+        }
+    }
+}
+
+/// The sprites to draw split into layers.
+#[derive(Debug, Clone)]
+pub struct SpriteLayers {
+    layers: Box<[Vec<Entity>]>,
+    sort_modes: Box<[SortMode]>,
+}
+
+impl Default for SpriteLayers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpriteLayers {
+    /// Constructs the layers without any sprites sorted by [`SortMode::Z`].
+    pub fn new() -> Self {
+        Self {
+            layers: vec![Vec::new(); u8::MAX as usize + 1].into_boxed_slice(),
+            sort_modes: vec![SortMode::default(); u8::MAX as usize + 1].into_boxed_slice(),
+        }
+    }
+    /// Sets the order the sprites on the layer are drawn in.
+    pub fn set_sort_mode(&mut self, layer: u8, mode: SortMode) {
+        self.sort_modes[layer as usize] = mode;
+    }
+    pub fn add_sprite(&mut self, entity: Entity, sprites: &ComponentPool<Sprite>) {
+        let sprite = sprites.get(entity).expect("Entity must have a sprite!");
+        self.layers[sprite.layer as usize].push(entity);
+    }
+    pub fn remove_sprite(&mut self, entity: Entity, sprites: &ComponentPool<Sprite>) {
+        let sprite = sprites.get(entity).expect("Entity must have a sprite!");
+        let layer = &mut self.layers[sprite.layer as usize];
+        let index = layer
+            .iter()
+            .position(|&other| other == entity)
+            .expect("Entity was not added in the first place!");
+        // Keep the order, so that the layer stays almost sorted.
+        layer.remove(index);
+    }
+    /// Sorts the layers and appends the commands drawing the visible sprites
+    /// overlapping the `view` to the `draw_list` layer by layer.
+    pub fn record(
+        &mut self,
+        view: Rect,
+        sprites: &ComponentPool<Sprite>,
+        transforms: &ComponentPool<Transform>,
+        draw_list: &mut DrawList,
+    ) -> RenderStats {
+        let mut stats = RenderStats::default();
+        for (index, layer) in self.layers.iter_mut().enumerate() {
+            match self.sort_modes[index] {
+                SortMode::Insertion => {}
+                SortMode::Z => insertion_sort_by(layer, |&a, &b| {
+                    sprites.get(a).unwrap().z < sprites.get(b).unwrap().z
+                }),
+                SortMode::Y => insertion_sort_by(layer, |&a, &b| {
+                    let bottom =
+                        |entity| sprite_bottom(sprites.get(entity).unwrap(), transforms, entity);
+                    bottom(a) < bottom(b)
+                }),
+            }
+            for &entity in layer.iter() {
+                let sprite = sprites.get(entity).unwrap();
+                if !sprite.is_visible {
+                    continue;
+                }
+                let (dest, rotation, pivot) = match sprite.source {
+                    SpriteSource::World(size) => {
+                        let transform = transforms
+                            .get(entity)
+                            .expect("All entities with world sprites must have transforms!");
+                        // The sprite can be rotated around the pivot,
+                        // so cull it by the circle it sweeps.
+                        let reach = (size * sprite.pivot.max(Vec2::ONE - sprite.pivot)).length();
+                        let bounds = Rect::new(
+                            transform.position.x - reach,
+                            transform.position.y - reach,
+                            reach * 2.,
+                            reach * 2.,
+                        );
+                        if !bounds.overlaps(&view) {
+                            stats.culled += 1;
+                            continue;
+                        }
+                        let origin = transform.position - size * sprite.pivot;
+                        (
+                            Rect::new(origin.x, origin.y, size.x, size.y),
+                            transform.rotation,
+                            Some(transform.position),
+                        )
+                    }
+                    SpriteSource::Ui(rect) => {
+                        if !rect.overlaps(&view) {
+                            stats.culled += 1;
+                            continue;
+                        }
+                        (rect, 0., None)
+                    }
+                };
+                draw_list.push(
+                    sprite.layer,
+                    DrawCommand::Texture(TextureCommand {
+                        texture: sprite.texture,
+                        dest,
+                        source: sprite.region,
+                        rotation,
+                        pivot,
+                        flip_x: sprite.flip_x,
+                        tint: sprite.tint,
+                        flash: sprite.flash_time / Sprite::FLASH_DURATION * sprite.tint.a,
+                    }),
+                );
+                stats.drawn += 1;
+            }
+        }
+        stats
+    }
+}
+
+/// Sorts the slice with the insertion sort keeping the order of the equal elements.
+///
+/// The sprites move little between the frames, so the layers are almost sorted
+/// and the insertion sort takes linear time on them.
+fn insertion_sort_by<T: Copy>(slice: &mut [T], mut is_less: impl FnMut(&T, &T) -> bool) {
+    for i in 1..slice.len() {
+        let mut j = i;
+        while j > 0 && is_less(&slice[j], &slice[j - 1]) {
+            slice.swap(j, j - 1);
+            j -= 1;
+        }
+    }
+}
+
+/// Returns the y coordinate of the bottom edge of the sprite.
+fn sprite_bottom(sprite: &Sprite, transforms: &ComponentPool<Transform>, entity: Entity) -> f32 {
+    match sprite.source {
+        SpriteSource::World(size) => {
+            transforms.get(entity).unwrap().position.y + size.y * (1. - sprite.pivot.y)
+        }
+        SpriteSource::Ui(rect) => rect.bottom(),
+    }
+}
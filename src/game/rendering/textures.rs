@@ -0,0 +1,254 @@
+use macroquad::{
+    color::BLANK,
+    math::{Rect, Vec2},
+    texture::{FilterMode, Image, Texture2D},
+};
+
+/// The identifier of a texture in the [`Textures`] registry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TextureId(pub u32);
+
+/// Where a texture ended up in the atlas.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    page: usize,
+    region: Rect,
+}
+
+/// A page of the atlas packed with the shelf algorithm: the textures are placed
+/// left to right on the shelves stacked top to bottom.
+///
+/// The page starts small and doubles in size while the textures don't fit, which keeps
+/// the textures placed so far where they are.
+#[derive(Clone)]
+struct Page {
+    image: Image,
+    filter: FilterMode,
+    cursor: (u16, u16),
+    shelf_height: u16,
+}
+
+/// A registry packing the textures the sprites refer to by [`TextureId`] into atlas pages.
+///
+/// The sprites don't own textures themselves, so that they can be created and
+/// recorded into a [`DrawList`](super::draw_list::DrawList) without a GPU. The sprites
+/// sharing a page are drawn from the same texture, which lets macroquad batch them
+/// into a single draw call. The textures are added at startup and then uploaded
+/// to the GPU all at once by [`Textures::build`].
+#[derive(Clone, Default)]
+pub struct Textures {
+    entries: Vec<Entry>,
+    /// The pages being packed, which are dropped once they are uploaded.
+    pages: Vec<Page>,
+    /// The uploaded pages, empty until the atlas is built.
+    textures: Vec<Texture2D>,
+}
+
+impl Textures {
+    /// The size of a new page unless the texture doesn't fit into it.
+    const PAGE_SIZE_MIN: u16 = 256;
+    /// The size the pages stop growing at. The larger textures get pages of their own.
+    const PAGE_SIZE_MAX: u16 = 2048;
+    /// The empty space around the textures, so that the filtering doesn't bleed into the neighbours.
+    const PADDING: u16 = 2;
+
+    /// Constructs an empty registry.
+    pub fn new() -> Self {
+        Default::default()
+    }
+    /// Packs the image into a page with the same filter mode and returns its identifier.
+    ///
+    /// The first page the image fits into is grown as needed. If there is none,
+    /// the image is put into a new page of the smallest power of two size that fits it.
+    ///
+    /// # Panics
+    /// Panics if the atlas is already built.
+    pub fn add(&mut self, image: &Image, filter: FilterMode) -> TextureId {
+        assert!(
+            self.textures.is_empty(),
+            "Textures must be added before the atlas is built!"
+        );
+        let size = (image.width + Self::PADDING, image.height + Self::PADDING);
+        let fit = self.pages.iter().enumerate().find_map(|(index, page)| {
+            (page.filter == filter)
+                .then(|| page.fit(size, Self::PAGE_SIZE_MAX))
+                .flatten()
+                .map(|(page_size, position)| (index, page_size, position))
+        });
+        let (page, (x, y)) = match fit {
+            Some((page, page_size, position)) => {
+                self.pages[page].grow(page_size);
+                (page, position)
+            }
+            None => {
+                let page_size = size
+                    .0
+                    .max(size.1)
+                    .next_power_of_two()
+                    .max(Self::PAGE_SIZE_MIN);
+                self.pages.push(Page::new(page_size, page_size, filter));
+                (self.pages.len() - 1, (0, 0))
+            }
+        };
+        self.pages[page].place(image, x, y, size);
+        let id = TextureId(self.entries.len() as u32);
+        self.entries.push(Entry {
+            page,
+            region: Rect::new(x as f32, y as f32, image.width as f32, image.height as f32),
+        });
+        id
+    }
+    /// Uploads the pages to the GPU and frees their images.
+    pub fn build(&mut self) {
+        self.textures = self
+            .pages
+            .drain(..)
+            .map(|page| {
+                let texture = Texture2D::from_image(&page.image);
+                texture.set_filter(page.filter);
+                texture
+            })
+            .collect();
+    }
+    /// Returns the size of the texture with the identifier.
+    pub fn size(&self, id: TextureId) -> Vec2 {
+        self.entry(id).region.size()
+    }
+    /// Returns the index of the page the texture with the identifier is on.
+    pub fn page(&self, id: TextureId) -> usize {
+        self.entry(id).page
+    }
+    /// Returns the page the texture with the identifier is on
+    /// and the region of the page it occupies.
+    ///
+    /// # Panics
+    /// Panics if the atlas is not built.
+    pub fn get(&self, id: TextureId) -> (&Texture2D, Rect) {
+        let entry = self.entry(id);
+        let texture = self
+            .textures
+            .get(entry.page)
+            .expect("Atlas must be built before drawing!");
+        (texture, entry.region)
+    }
+    fn entry(&self, id: TextureId) -> Entry {
+        *self
+            .entries
+            .get(id.0 as usize)
+            .expect("Texture must be added to the registry!")
+    }
+}
+
+impl Page {
+    fn new(width: u16, height: u16, filter: FilterMode) -> Self {
+        Self {
+            image: Image::gen_image_color(width, height, BLANK),
+            filter,
+            cursor: (0, 0),
+            shelf_height: 0,
+        }
+    }
+    fn size(&self) -> (u16, u16) {
+        (self.image.width, self.image.height)
+    }
+    /// Returns the position the area of the specified size would be placed at
+    /// if the page had the supplied size or `None` if it doesn't fit.
+    fn reserve(&self, size: (u16, u16), page_size: (u16, u16)) -> Option<(u16, u16)> {
+        let (mut x, mut y) = self.cursor;
+        if x + size.0 > page_size.0 {
+            // Start a new shelf.
+            x = 0;
+            y += self.shelf_height;
+        }
+        (x + size.0 <= page_size.0 && y + size.1 <= page_size.1).then_some((x, y))
+    }
+    /// Returns the smallest size the page can be doubled to without exceeding `max`
+    /// that fits the area of the specified size alongside the position the area would
+    /// be placed at, or `None` if it doesn't fit.
+    fn fit(&self, size: (u16, u16), max: u16) -> Option<((u16, u16), (u16, u16))> {
+        let mut page_size = self.size();
+        loop {
+            if let Some(position) = self.reserve(size, page_size) {
+                return Some((page_size, position));
+            }
+            if page_size.0.max(page_size.1) >= max {
+                return None;
+            }
+            page_size = (page_size.0 * 2, page_size.1 * 2);
+        }
+    }
+    /// Enlarges the page to the specified size keeping the textures where they are.
+    fn grow(&mut self, size: (u16, u16)) {
+        if size == self.size() {
+            return;
+        }
+        let mut image = Image::gen_image_color(size.0, size.1, BLANK);
+        blit(&mut image, &self.image, 0, 0);
+        self.image = image;
+    }
+    /// Copies the image to the reserved position and moves the cursor past it.
+    fn place(&mut self, image: &Image, x: u16, y: u16, size: (u16, u16)) {
+        if y != self.cursor.1 {
+            self.shelf_height = 0;
+        }
+        self.cursor = (x + size.0, y);
+        self.shelf_height = self.shelf_height.max(size.1);
+        blit(&mut self.image, image, x, y);
+    }
+}
+
+/// Copies the `source` image into the `dest` image with its top left corner at \[`x`, `y`\].
+fn blit(dest: &mut Image, source: &Image, x: u16, y: u16) {
+    let row_len = source.width as usize * 4;
+    let dest_row_len = dest.width as usize * 4;
+    for (row, pixels) in source.bytes.chunks_exact(row_len).enumerate() {
+        let start = (y as usize + row) * dest_row_len + x as usize * 4;
+        dest.bytes[start..start + row_len].copy_from_slice(pixels);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use macroquad::color::WHITE;
+
+    use super::*;
+
+    fn image(width: u16, height: u16) -> Image {
+        Image::gen_image_color(width, height, WHITE)
+    }
+
+    #[test]
+    fn pages_grow_in_powers_of_two() {
+        let mut textures = Textures::new();
+        let first = textures.add(&image(100, 100), FilterMode::Linear);
+        assert_eq!(textures.pages[0].size(), (256, 256));
+        // Doesn't fit next to the first one until the page grows.
+        let second = textures.add(&image(200, 200), FilterMode::Linear);
+        assert_eq!(textures.pages.len(), 1);
+        assert_eq!(textures.pages[0].size(), (512, 512));
+        assert_eq!(textures.entry(first).region, Rect::new(0., 0., 100., 100.));
+        assert_eq!(
+            textures.entry(second).region,
+            Rect::new(102., 0., 200., 200.)
+        );
+        // The texture is copied as well as kept in place.
+        let bytes = &textures.pages[0].image.bytes;
+        assert_eq!(bytes[..4], [255; 4]);
+        assert_eq!(bytes[(101 * 512 + 101) * 4..][..4], [0; 4]);
+    }
+
+    #[test]
+    fn pages_are_split_by_filter_and_size() {
+        let mut textures = Textures::new();
+        let linear = textures.add(&image(10, 10), FilterMode::Linear);
+        let nearest = textures.add(&image(10, 10), FilterMode::Nearest);
+        assert_ne!(textures.page(linear), textures.page(nearest));
+        // Larger than the largest page, so it gets a page of its own.
+        let large = textures.add(&image(3000, 10), FilterMode::Linear);
+        assert_eq!(textures.pages[textures.page(large)].size(), (4096, 4096));
+        // The first page still has room.
+        let small = textures.add(&image(10, 10), FilterMode::Linear);
+        assert_eq!(textures.page(small), textures.page(linear));
+        assert_eq!(textures.pages[0].size(), (256, 256));
+    }
+}
@@ -0,0 +1,188 @@
+use macroquad::{
+    color::WHITE,
+    math::{Rect, Vec2},
+};
+
+use crate::{
+    ecs::component::Component,
+    game::{
+        collision::statics::StaticShape,
+        rendering::{
+            draw_list::{DrawCommand, DrawList, TextureCommand},
+            textures::TextureId,
+        },
+    },
+};
+
+/// A component for a grid of tiles drawn from a tileset atlas, placed with its top left corner
+/// at the [`Transform`](crate::game::transform::Transform) of the entity.
+#[derive(Debug, Clone)]
+pub struct Tilemap {
+    /// The tileset atlas.
+    pub texture: TextureId,
+    /// The sprite layer the tiles are drawn on before the sprites.
+    pub layer: u8,
+    /// The size of a tile in the tileset atlas.
+    tile_region: Vec2,
+    tileset_columns: usize,
+    /// `true` for the tiles of the tileset with collision.
+    solid: Box<[bool]>,
+    tile_size: f32,
+    width: usize,
+    height: usize,
+    /// The tile layers drawn on top of each other, each storing the rows of the map one by one.
+    layers: Box<[Box<[Option<usize>]>]>,
+}
+
+impl Component for Tilemap {}
+
+impl Tilemap {
+    /// Parses a map file using the texture as the tileset atlas.
+    ///
+    /// The map file consists of the lines with a keyword followed by its arguments:
+    /// - `tileset <columns> <rows>` - the number of the tiles in the atlas;
+    /// - `solid <tile>...` - the tiles with collision;
+    /// - `tile_size <size>` - the size of a tile in the world;
+    /// - `layer` - starts a tile layer. The following lines up to the next keyword are the rows
+    ///   of the layer with the whitespace separated tile indices, where `.` is an empty cell.
+    ///
+    /// The empty lines and the lines starting with `#` are skipped.
+    /// The map files are baked into the game, so the invalid ones panic.
+    pub fn parse(source: &str, texture: TextureId, texture_size: Vec2, layer: u8) -> Self {
+        let mut tileset = None;
+        let mut solid_tiles = Vec::new();
+        let mut tile_size = None;
+        let mut layers: Vec<Vec<Vec<Option<usize>>>> = Vec::new();
+        let lines = source
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        for line in lines {
+            let mut words = line.split_whitespace();
+            let number = |word: &str| -> usize { word.parse().expect("Map must contain numbers!") };
+            match words.next().unwrap() {
+                "tileset" => {
+                    let columns = number(words.next().expect("Tileset must have columns!"));
+                    let rows = number(words.next().expect("Tileset must have rows!"));
+                    tileset = Some((columns, rows));
+                }
+                "solid" => solid_tiles.extend(words.map(number)),
+                "tile_size" => {
+                    let size = words.next().expect("Tile size must be specified!");
+                    tile_size = Some(size.parse::<f32>().expect("Tile size must be a number!"));
+                }
+                "layer" => layers.push(Vec::new()),
+                _ => {
+                    let rows = layers
+                        .last_mut()
+                        .expect("Tile rows must belong to a layer!");
+                    let row = line
+                        .split_whitespace()
+                        .map(|word| (word != ".").then(|| number(word)))
+                        .collect();
+                    rows.push(row);
+                }
+            }
+        }
+        let (columns, rows) = tileset.expect("Map must specify the tileset!");
+        let tile_size = tile_size.expect("Map must specify the tile size!");
+        assert!(!layers.is_empty(), "Map must have layers!");
+        let height = layers[0].len();
+        let width = layers[0].first().map_or(0, Vec::len);
+        let tile_count = columns * rows;
+        let mut solid = vec![false; tile_count].into_boxed_slice();
+        for tile in solid_tiles {
+            assert!(tile < tile_count, "Solid tiles must be in the tileset!");
+            solid[tile] = true;
+        }
+        let layers = layers
+            .into_iter()
+            .map(|rows| {
+                assert!(rows.len() == height, "Map layers must have the same size!");
+                let cells: Box<[_]> = rows
+                    .into_iter()
+                    .inspect(|row| assert!(row.len() == width, "Map rows must have the same size!"))
+                    .flatten()
+                    .collect();
+                assert!(
+                    cells.iter().flatten().all(|&tile| tile < tile_count),
+                    "Map tiles must be in the tileset!"
+                );
+                cells
+            })
+            .collect();
+        Self {
+            texture,
+            layer,
+            tile_region: texture_size / Vec2::new(columns as f32, rows as f32),
+            tileset_columns: columns,
+            solid,
+            tile_size,
+            width,
+            height,
+            layers,
+        }
+    }
+    /// Returns the area covered by the map with its top left corner at `origin`.
+    pub fn bounds(&self, origin: Vec2) -> Rect {
+        Rect::new(
+            origin.x,
+            origin.y,
+            self.width as f32 * self.tile_size,
+            self.height as f32 * self.tile_size,
+        )
+    }
+    /// Returns the rectangles covering the cells with a solid tile on any layer.
+    pub fn solid_shapes(&self, origin: Vec2) -> Vec<StaticShape> {
+        let rows = (0..self.height).map(|y| {
+            (0..self.width).map(move |x| {
+                let index = y * self.width + x;
+                self.layers
+                    .iter()
+                    .any(|layer| layer[index].is_some_and(|tile| self.solid[tile]))
+            })
+        });
+        StaticShape::tiles(origin, self.tile_size, rows).collect()
+    }
+    /// Records the commands drawing the tiles overlapping the `view` layer by layer.
+    pub fn record(&self, origin: Vec2, view: Rect, draw_list: &mut DrawList) {
+        let first = ((view.point() - origin) / self.tile_size)
+            .floor()
+            .max(Vec2::ZERO);
+        let last = ((view.point() + view.size() - origin) / self.tile_size).ceil();
+        let columns = first.x as usize..(last.x.max(0.) as usize).min(self.width);
+        let rows = first.y as usize..(last.y.max(0.) as usize).min(self.height);
+        for layer in &self.layers {
+            for y in rows.clone() {
+                for x in columns.clone() {
+                    let Some(tile) = layer[y * self.width + x] else {
+                        continue;
+                    };
+                    let tile_position = Vec2::new(
+                        (tile % self.tileset_columns) as f32,
+                        (tile / self.tileset_columns) as f32,
+                    ) * self.tile_region;
+                    let position = origin + Vec2::new(x as f32, y as f32) * self.tile_size;
+                    draw_list.push(
+                        self.layer,
+                        DrawCommand::Texture(TextureCommand {
+                            texture: self.texture,
+                            dest: Rect::new(position.x, position.y, self.tile_size, self.tile_size),
+                            source: Some(Rect::new(
+                                tile_position.x,
+                                tile_position.y,
+                                self.tile_region.x,
+                                self.tile_region.y,
+                            )),
+                            rotation: 0.,
+                            pivot: None,
+                            flip_x: false,
+                            tint: WHITE,
+                            flash: 0.,
+                        }),
+                    );
+                }
+            }
+        }
+    }
+}
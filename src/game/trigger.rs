@@ -0,0 +1,274 @@
+use macroquad::math::Vec2;
+
+use crate::{
+    Context,
+    ecs::{
+        component::{Component, ComponentPool},
+        entity::Entity,
+    },
+    game::{
+        collision::{Collider, SpatialIndex},
+        transform::Transform,
+    },
+};
+
+/// A function called with the trigger entity and the entity that entered or left it.
+pub type TriggerCallback = fn(&mut Context, Entity, Entity);
+
+/// A component reporting the entities entering and leaving the [`Collider`] of the entity.
+///
+/// The collider of the trigger should have no body, so it never blocks anything,
+/// and its `monitoring` mask determines the layers of the entities that are reported.
+/// The trigger stays in place unless it is attached to an `anchor` entity, in which
+/// case it is moved to the position of the anchor offset by `offset` every tick.
+#[derive(Debug, Clone)]
+pub struct Trigger {
+    /// The entity the trigger follows or `None` if the trigger doesn't move.
+    pub anchor: Option<Entity>,
+    /// The offset of the trigger from its anchor.
+    pub offset: Vec2,
+    /// Called for every entity that entered the trigger.
+    pub on_enter: Option<TriggerCallback>,
+    /// Called for every entity that left the trigger.
+    pub on_exit: Option<TriggerCallback>,
+    overlapping: Vec<Entity>,
+    entered: Vec<Entity>,
+    exited: Vec<Entity>,
+}
+
+impl Component for Trigger {}
+
+impl Trigger {
+    /// Constructs a trigger without callbacks that follows the `anchor` if there is one.
+    pub fn new(anchor: Option<Entity>) -> Self {
+        Self {
+            anchor,
+            offset: Vec2::ZERO,
+            on_enter: None,
+            on_exit: None,
+            overlapping: Vec::new(),
+            entered: Vec::new(),
+            exited: Vec::new(),
+        }
+    }
+    /// Returns the entities inside of the trigger since the last tick.
+    pub fn overlapping(&self) -> &[Entity] {
+        &self.overlapping
+    }
+    /// Returns the entities that entered the trigger during the last tick.
+    pub fn entered(&self) -> &[Entity] {
+        &self.entered
+    }
+    /// Returns the entities that left the trigger during the last tick.
+    ///
+    /// The entities destroyed while inside of the trigger are reported here as well,
+    /// so they might not be alive anymore.
+    pub fn exited(&self) -> &[Entity] {
+        &self.exited
+    }
+    /// Replaces the overlapping entities with the new ones and
+    /// records which of them have entered or left the trigger.
+    fn set_overlapping(&mut self, overlapping: Vec<Entity>) {
+        self.entered.clear();
+        self.exited.clear();
+        self.entered.extend(
+            overlapping
+                .iter()
+                .filter(|entity| !self.overlapping.contains(entity)),
+        );
+        self.exited.extend(
+            self.overlapping
+                .iter()
+                .filter(|entity| !overlapping.contains(entity)),
+        );
+        self.overlapping = overlapping;
+    }
+}
+
+/// Moves the triggers to their anchors.
+///
+/// Should be run before the collision index is updated.
+pub fn move_triggers(context: &mut Context) {
+    let triggers = context.world.borrow_pool::<Trigger>();
+    let mut transforms = context.world.borrow_pool_mut::<Transform>();
+    for (entity, trigger) in triggers.iter() {
+        let Some(anchor) = trigger.anchor else {
+            continue;
+        };
+        if !context.world.is_entity_alive(anchor) {
+            continue;
+        }
+        let Some(position) = transforms.get(anchor).map(|transform| transform.position) else {
+            continue;
+        };
+        transforms
+            .get_mut(entity)
+            .expect("All triggers must have a Transform component!")
+            .position = position + trigger.offset;
+    }
+}
+
+/// Returns the entities colliding with the trigger whose layers are in its `monitoring` mask.
+fn overlapping_entities(
+    collisions: &dyn SpatialIndex,
+    transforms: &ComponentPool<Transform>,
+    colliders: &ComponentPool<Collider>,
+    entity: Entity,
+) -> Vec<Entity> {
+    let monitoring = colliders
+        .get(entity)
+        .expect("All triggers must have a Collider component!")
+        .monitoring;
+    let mut res = collisions.collisions(transforms, colliders, entity);
+    // The collisions are also reported when the other collider monitors the trigger.
+    res.retain(|&other| {
+        colliders
+            .get(other)
+            .unwrap()
+            .monitorable
+            .overlaps(monitoring)
+    });
+    res
+}
+
+/// Detects the entities entering and leaving the triggers and runs the callbacks.
+///
+/// The callbacks are skipped if the trigger or the other entity is not alive anymore,
+/// e.g. because it was destroyed by one of the callbacks before.
+///
+/// Should be run after the collision index is updated.
+pub fn detect_triggers(context: &mut Context) {
+    let mut callbacks: Vec<(TriggerCallback, Entity, Entity)> = Vec::new();
+    {
+        let mut triggers = context.world.borrow_pool_mut::<Trigger>();
+        let transforms = context.world.borrow_pool::<Transform>();
+        let colliders = context.world.borrow_pool::<Collider>();
+        for (entity, trigger) in triggers.iter_mut() {
+            let overlapping =
+                overlapping_entities(context.collisions.as_ref(), &transforms, &colliders, entity);
+            trigger.set_overlapping(overlapping);
+            if let Some(on_enter) = trigger.on_enter {
+                callbacks.extend(
+                    trigger
+                        .entered()
+                        .iter()
+                        .map(|&other| (on_enter, entity, other)),
+                );
+            }
+            if let Some(on_exit) = trigger.on_exit {
+                callbacks.extend(
+                    trigger
+                        .exited()
+                        .iter()
+                        .map(|&other| (on_exit, entity, other)),
+                );
+            }
+        }
+    }
+    for (callback, entity, other) in callbacks {
+        if context.world.is_entity_alive(entity) && context.world.is_entity_alive(other) {
+            callback(context, entity, other);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use macroquad::math::Rect;
+
+    use super::*;
+    use crate::{
+        ecs::entity::World,
+        game::collision::{
+            Broadphase, Shape,
+            layer::{CollisionLayer, CollisionMask},
+        },
+    };
+
+    fn spawn(
+        world: &mut World,
+        position: Vec2,
+        monitoring: CollisionMask,
+        layer: CollisionLayer,
+    ) -> Entity {
+        let entity = world.create_entity();
+        world.borrow_pool_mut::<Transform>().insert(
+            entity,
+            Transform {
+                position,
+                rotation: 0.,
+            },
+        );
+        world.borrow_pool_mut::<Collider>().insert(
+            entity,
+            Collider {
+                shape: Shape::Circle { radius: 10. },
+                monitoring,
+                monitorable: layer.mask(),
+                body: None,
+            },
+        );
+        entity
+    }
+
+    #[test]
+    fn set_overlapping_reports_entered_and_exited() {
+        let mut world = World::new();
+        let [a, b, c] = [(); 3].map(|_| world.create_entity());
+        let mut trigger = Trigger::new(None);
+        trigger.set_overlapping(vec![a, b]);
+        assert_eq!(trigger.entered(), [a, b]);
+        assert!(trigger.exited().is_empty());
+        trigger.set_overlapping(vec![b, c]);
+        assert_eq!(trigger.overlapping(), [b, c]);
+        assert_eq!(trigger.entered(), [c]);
+        assert_eq!(trigger.exited(), [a]);
+        trigger.set_overlapping(vec![b, c]);
+        assert!(trigger.entered().is_empty());
+        assert!(trigger.exited().is_empty());
+        trigger.set_overlapping(Vec::new());
+        assert!(trigger.entered().is_empty());
+        assert_eq!(trigger.exited(), [b, c]);
+    }
+
+    #[test]
+    fn overlapping_entities_are_filtered_by_the_trigger_mask() {
+        let mut world = World::new();
+        world.register_type::<Transform>();
+        world.register_type::<Collider>();
+        let trigger = spawn(
+            &mut world,
+            Vec2::ZERO,
+            CollisionLayer::Enemy.mask(),
+            CollisionLayer::Trigger,
+        );
+        let enemy = spawn(
+            &mut world,
+            Vec2::X,
+            CollisionMask::NONE,
+            CollisionLayer::Enemy,
+        );
+        // The pickup monitors the trigger, but the trigger doesn't monitor the pickups.
+        spawn(
+            &mut world,
+            Vec2::Y,
+            CollisionLayer::Trigger.mask(),
+            CollisionLayer::Pickup,
+        );
+        // Too far away to overlap.
+        spawn(
+            &mut world,
+            Vec2::splat(100.),
+            CollisionMask::NONE,
+            CollisionLayer::Enemy,
+        );
+        let transforms = world.borrow_pool::<Transform>();
+        let colliders = world.borrow_pool::<Collider>();
+        let mut index = Broadphase::Grid.build();
+        index.update(&transforms, &colliders, Rect::new(-200., -200., 400., 400.));
+        assert_eq!(
+            overlapping_entities(index.as_ref(), &transforms, &colliders, trigger),
+            [enemy]
+        );
+    }
+}
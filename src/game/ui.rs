@@ -0,0 +1,598 @@
+use macroquad::{
+    color::{Color, WHITE, YELLOW},
+    input::{
+        KeyCode, MouseButton, TouchPhase, is_key_down, is_key_pressed, is_mouse_button_pressed,
+        is_mouse_button_released, mouse_position, touches,
+    },
+    math::{Rect, Vec2},
+    text::measure_text,
+};
+
+use crate::game::{
+    gamepad::{GamepadButton, Gamepads},
+    rendering::{Screen, textures::TextureId},
+};
+
+pub mod menus;
+
+/// A command to draw a piece of the UI in virtual-screen coordinates.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UiCommand {
+    Rect {
+        rect: Rect,
+        color: Color,
+    },
+    Outline {
+        rect: Rect,
+        thickness: f32,
+        color: Color,
+    },
+    /// The text with the left end of its baseline at `position`.
+    Text {
+        text: String,
+        position: Vec2,
+        font_size: f32,
+        color: Color,
+    },
+    Texture {
+        texture: TextureId,
+        dest: Rect,
+    },
+}
+
+/// The input driving the UI for a frame.
+///
+/// It's polled from the keyboard, the mouse, the touches and the gamepads.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UiInput {
+    /// The position of the pointer on the virtual screen or `None` if there is no pointer.
+    pub pointer: Option<Vec2>,
+    /// `true` if the pointer was pressed during the frame.
+    pub is_pressed: bool,
+    /// `true` if the pointer was released during the frame.
+    pub is_released: bool,
+    /// The direction to move the focus in, if any.
+    pub navigate: Option<Vec2>,
+    /// `true` if the focus must move to the next widget in the order they were added.
+    pub next: bool,
+    /// `true` if the focus must move to the previous widget in the order they were added.
+    pub previous: bool,
+    /// `true` if the focused widget must be activated.
+    pub activate: bool,
+}
+
+impl UiInput {
+    /// Polls the input of the frame mapping the pointer from the window onto the virtual screen.
+    pub fn poll(screen: &Screen, gamepads: &Gamepads) -> Self {
+        let touch = touches().into_iter().next();
+        let (pointer, is_pressed, is_released) = match touch {
+            Some(touch) => (
+                touch.position,
+                touch.phase == TouchPhase::Started,
+                matches!(touch.phase, TouchPhase::Ended | TouchPhase::Cancelled),
+            ),
+            None => (
+                mouse_position().into(),
+                is_mouse_button_pressed(MouseButton::Left),
+                is_mouse_button_released(MouseButton::Left),
+            ),
+        };
+        let is_shift_down = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+        let navigate = [
+            (KeyCode::Up, GamepadButton::DPadUp, Vec2::NEG_Y),
+            (KeyCode::Down, GamepadButton::DPadDown, Vec2::Y),
+            (KeyCode::Left, GamepadButton::DPadLeft, Vec2::NEG_X),
+            (KeyCode::Right, GamepadButton::DPadRight, Vec2::X),
+        ]
+        .into_iter()
+        .find(|&(key, button, _)| is_key_pressed(key) || gamepads.is_pressed(button))
+        .map(|(_, _, direction)| direction);
+        Self {
+            pointer: Some(screen.window_to_screen(pointer)),
+            is_pressed,
+            is_released,
+            navigate,
+            next: (is_key_pressed(KeyCode::Tab) && !is_shift_down)
+                || gamepads.is_pressed(GamepadButton::RightShoulder),
+            previous: (is_key_pressed(KeyCode::Tab) && is_shift_down)
+                || gamepads.is_pressed(GamepadButton::LeftShoulder),
+            activate: is_key_pressed(KeyCode::Enter)
+                || is_key_pressed(KeyCode::Space)
+                || gamepads.is_pressed(GamepadButton::South),
+        }
+    }
+}
+
+/// The identifier of an interactive widget.
+///
+/// The widgets are numbered in the order they are added, so the identifiers
+/// stay the same between the frames as long as the UI keeps its structure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WidgetId(u32);
+
+/// A resource keeping the state of the UI between the frames.
+#[derive(Debug, Clone, Default)]
+pub struct UiState {
+    focus: Option<WidgetId>,
+    /// The widget the pointer was pressed on.
+    active: Option<WidgetId>,
+    /// The interactive widgets of the last frame in the order they were added.
+    focusables: Vec<(WidgetId, Rect)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Direction {
+    Vertical,
+    Horizontal,
+    /// Equally sized cells filled left to right, top to bottom.
+    Grid {
+        columns: usize,
+        cell: Vec2,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    rect: Rect,
+    direction: Direction,
+    /// The offset of the next widget from the top left corner.
+    cursor: Vec2,
+    /// The number of the widgets placed so far.
+    index: usize,
+}
+
+/// The result of adding an interactive widget.
+#[derive(Debug, Clone, Copy)]
+struct Interaction {
+    is_hovered: bool,
+    is_focused: bool,
+    is_clicked: bool,
+}
+
+/// An immediate-mode UI built anew every frame.
+///
+/// The widgets are placed by the innermost layout and record the commands drawing them,
+/// which are drawn by the [`Screen`] over the HUD. The focus moves between the interactive
+/// widgets of the previous frame, since the widgets of the current one are not known yet.
+pub struct Ui<'a> {
+    state: &'a mut UiState,
+    input: UiInput,
+    commands: Vec<UiCommand>,
+    layouts: Vec<Layout>,
+    next_id: u32,
+    focusables: Vec<(WidgetId, Rect)>,
+    /// The rectangle of the last widget and `true` if it's hovered or focused.
+    last: Option<(Rect, bool)>,
+    tooltip: Option<(String, Rect)>,
+}
+
+impl<'a> Ui<'a> {
+    pub const FONT_SIZE: f32 = 28.;
+    /// The distance from the top of a line of text to its baseline.
+    const ASCENT: f32 = Self::FONT_SIZE * 0.75;
+    /// The space between the widgets and between the borders and the contents.
+    pub const SPACING: f32 = 10.;
+    const PANEL_COLOR: Color = Color::new(0.1, 0.1, 0.15, 0.9);
+    const BUTTON_COLOR: Color = Color::new(0.25, 0.25, 0.35, 1.);
+    const HOVERED_COLOR: Color = Color::new(0.35, 0.35, 0.5, 1.);
+    const PRESSED_COLOR: Color = Color::new(0.15, 0.15, 0.2, 1.);
+    const FOCUS_COLOR: Color = YELLOW;
+
+    /// Starts the UI of a frame covering the virtual screen of the specified size
+    /// with a vertical layout and moves the focus according to the input.
+    pub fn new(state: &'a mut UiState, input: UiInput, screen_size: Vec2) -> Self {
+        let is_focus_lost = state
+            .focus
+            .is_some_and(|focus| !state.focusables.iter().any(|&(id, _)| id == focus));
+        if is_focus_lost {
+            state.focus = None;
+        }
+        navigate(state, &input);
+        Self {
+            state,
+            input,
+            commands: Vec::new(),
+            layouts: vec![Layout {
+                rect: Rect::new(0., 0., screen_size.x, screen_size.y),
+                direction: Direction::Vertical,
+                cursor: Vec2::ZERO,
+                index: 0,
+            }],
+            next_id: 0,
+            focusables: Vec::new(),
+            last: None,
+            tooltip: None,
+        }
+    }
+    /// Finishes the UI returning the commands drawing it.
+    pub fn finish(mut self) -> Vec<UiCommand> {
+        if let Some((text, anchor)) = self.tooltip.take() {
+            self.draw_tooltip(&text, anchor);
+        }
+        if self.input.is_released {
+            self.state.active = None;
+        }
+        self.state.focusables = self.focusables;
+        self.commands
+    }
+    /// Fills the whole screen with the color, e.g. to dim the game behind a menu.
+    pub fn backdrop(&mut self, color: Color) {
+        let rect = self.layouts[0].rect;
+        self.commands.push(UiCommand::Rect { rect, color });
+    }
+    /// Adds a panel at the rectangle with its contents laid out vertically.
+    pub fn panel(&mut self, rect: Rect, contents: impl FnOnce(&mut Self)) {
+        self.commands.push(UiCommand::Rect {
+            rect,
+            color: Self::PANEL_COLOR,
+        });
+        self.commands.push(UiCommand::Outline {
+            rect,
+            thickness: 2.,
+            color: WHITE,
+        });
+        let inner = Rect::new(
+            rect.x + Self::SPACING,
+            rect.y + Self::SPACING,
+            rect.w - Self::SPACING * 2.,
+            rect.h - Self::SPACING * 2.,
+        );
+        self.with_layout(inner, Direction::Vertical, contents);
+        self.last = Some((rect, false));
+    }
+    /// Adds an area of the specified size with its contents laid out top to bottom.
+    pub fn vertical(&mut self, size: Vec2, contents: impl FnOnce(&mut Self)) {
+        let rect = self.allocate(size);
+        self.with_layout(rect, Direction::Vertical, contents);
+    }
+    /// Adds an area of the specified size with its contents laid out left to right.
+    pub fn horizontal(&mut self, size: Vec2, contents: impl FnOnce(&mut Self)) {
+        let rect = self.allocate(size);
+        self.with_layout(rect, Direction::Horizontal, contents);
+    }
+    /// Adds a grid of the cells of the specified size, which its contents fill
+    /// left to right, top to bottom.
+    pub fn grid(
+        &mut self,
+        columns: usize,
+        rows: usize,
+        cell: Vec2,
+        contents: impl FnOnce(&mut Self),
+    ) {
+        assert!(columns != 0, "Grid must have columns!");
+        let size = Vec2::new(
+            cell.x * columns as f32 + Self::SPACING * (columns as f32 - 1.),
+            cell.y * rows as f32 + Self::SPACING * (rows as f32 - 1.).max(0.),
+        );
+        let rect = self.allocate(size);
+        self.with_layout(rect, Direction::Grid { columns, cell }, contents);
+    }
+    pub fn label(&mut self, text: &str) {
+        let size = text_size(text);
+        let rect = self.allocate(size);
+        self.push_text(text, rect.point(), WHITE);
+        self.last = Some((rect, false));
+    }
+    pub fn icon(&mut self, texture: TextureId, size: Vec2) {
+        let dest = self.allocate(size);
+        self.commands.push(UiCommand::Texture { texture, dest });
+        let is_hovered = self.is_hovered(dest);
+        self.last = Some((dest, is_hovered));
+    }
+    /// Adds a button with the text and returns `true` if it was clicked or activated.
+    pub fn button(&mut self, text: &str) -> bool {
+        let rect = self.allocate(text_size(text) + Vec2::splat(Self::SPACING * 2.));
+        let interaction = self.interact(rect);
+        self.push_button(rect, interaction);
+        self.push_text(text, rect.point() + Vec2::splat(Self::SPACING), WHITE);
+        interaction.is_clicked
+    }
+    /// Adds a button with the texture and returns `true` if it was clicked or activated.
+    pub fn icon_button(&mut self, texture: TextureId, size: Vec2) -> bool {
+        let rect = self.allocate(size + Vec2::splat(Self::SPACING * 2.));
+        let interaction = self.interact(rect);
+        self.push_button(rect, interaction);
+        let dest = Rect::new(
+            rect.x + Self::SPACING,
+            rect.y + Self::SPACING,
+            size.x,
+            size.y,
+        );
+        self.commands.push(UiCommand::Texture { texture, dest });
+        interaction.is_clicked
+    }
+    /// Shows the text next to the last widget while it's hovered or focused.
+    pub fn tooltip(&mut self, text: &str) {
+        if let Some((rect, true)) = self.last {
+            self.tooltip = Some((text.to_owned(), rect));
+        }
+    }
+    fn with_layout(&mut self, rect: Rect, direction: Direction, contents: impl FnOnce(&mut Self)) {
+        self.layouts.push(Layout {
+            rect,
+            direction,
+            cursor: Vec2::ZERO,
+            index: 0,
+        });
+        contents(self);
+        self.layouts.pop();
+        self.last = Some((rect, false));
+    }
+    /// Returns the rectangle for a widget of the specified size placed by the innermost layout.
+    fn allocate(&mut self, size: Vec2) -> Rect {
+        let layout = self.layouts.last_mut().unwrap();
+        let origin = layout.rect.point();
+        let rect = match layout.direction {
+            Direction::Vertical => {
+                let rect = Rect::new(origin.x, origin.y + layout.cursor.y, size.x, size.y);
+                layout.cursor.y += size.y + Self::SPACING;
+                rect
+            }
+            Direction::Horizontal => {
+                let rect = Rect::new(origin.x + layout.cursor.x, origin.y, size.x, size.y);
+                layout.cursor.x += size.x + Self::SPACING;
+                rect
+            }
+            Direction::Grid { columns, cell } => {
+                let (column, row) = (layout.index % columns, layout.index / columns);
+                let offset = Vec2::new(column as f32, row as f32) * (cell + Self::SPACING);
+                Rect::new(origin.x + offset.x, origin.y + offset.y, cell.x, cell.y)
+            }
+        };
+        layout.index += 1;
+        rect
+    }
+    fn is_hovered(&self, rect: Rect) -> bool {
+        self.input
+            .pointer
+            .is_some_and(|pointer| rect.contains(pointer))
+    }
+    /// Registers an interactive widget at the rectangle and handles the input.
+    fn interact(&mut self, rect: Rect) -> Interaction {
+        let id = WidgetId(self.next_id);
+        self.next_id += 1;
+        self.focusables.push((id, rect));
+        let is_hovered = self.is_hovered(rect);
+        if is_hovered && self.input.is_pressed {
+            self.state.active = Some(id);
+            self.state.focus = Some(id);
+        }
+        let is_focused = self.state.focus == Some(id);
+        let is_clicked = (is_hovered && self.input.is_released && self.state.active == Some(id))
+            || (is_focused && self.input.activate);
+        self.last = Some((rect, is_hovered || is_focused));
+        Interaction {
+            is_hovered,
+            is_focused,
+            is_clicked,
+        }
+    }
+    fn push_button(&mut self, rect: Rect, interaction: Interaction) {
+        let color = if interaction.is_hovered && self.state.active.is_some() {
+            Self::PRESSED_COLOR
+        } else if interaction.is_hovered {
+            Self::HOVERED_COLOR
+        } else {
+            Self::BUTTON_COLOR
+        };
+        self.commands.push(UiCommand::Rect { rect, color });
+        if interaction.is_focused {
+            self.commands.push(UiCommand::Outline {
+                rect,
+                thickness: 3.,
+                color: Self::FOCUS_COLOR,
+            });
+        }
+    }
+    /// Adds the text with the top left corner at `position`.
+    fn push_text(&mut self, text: &str, position: Vec2, color: Color) {
+        self.commands.push(UiCommand::Text {
+            text: text.to_owned(),
+            position: position + Vec2::new(0., Self::ASCENT),
+            font_size: Self::FONT_SIZE,
+            color,
+        });
+    }
+    /// Draws the tooltip below the anchor keeping it on the screen.
+    fn draw_tooltip(&mut self, text: &str, anchor: Rect) {
+        let screen = self.layouts[0].rect;
+        let size = text_size(text) + Vec2::splat(Self::SPACING * 2.);
+        let mut origin = Vec2::new(anchor.x, anchor.bottom() + Self::SPACING / 2.);
+        if origin.y + size.y > screen.bottom() {
+            origin.y = anchor.y - Self::SPACING / 2. - size.y;
+        }
+        origin.x = origin.x.min(screen.right() - size.x).max(screen.x);
+        let rect = Rect::new(origin.x, origin.y, size.x, size.y);
+        self.commands.push(UiCommand::Rect {
+            rect,
+            color: Self::PANEL_COLOR,
+        });
+        self.commands.push(UiCommand::Outline {
+            rect,
+            thickness: 1.,
+            color: WHITE,
+        });
+        self.push_text(text, origin + Vec2::splat(Self::SPACING), WHITE);
+    }
+}
+
+/// Returns the size the text occupies when drawn with [`Ui::FONT_SIZE`].
+///
+/// The height is the same for all texts, so that the widgets line up.
+fn text_size(text: &str) -> Vec2 {
+    let dimensions = measure_text(text, None, Ui::FONT_SIZE as u16, 1.);
+    Vec2::new(dimensions.width, Ui::FONT_SIZE)
+}
+
+/// Moves the focus between the widgets of the last frame according to the input.
+fn navigate(state: &mut UiState, input: &UiInput) {
+    let focusables = &state.focusables;
+    if focusables.is_empty() {
+        return;
+    }
+    let current = state
+        .focus
+        .and_then(|focus| focusables.iter().position(|&(id, _)| id == focus));
+    let next = if input.next {
+        current.map_or(0, |index| (index + 1) % focusables.len())
+    } else if input.previous {
+        current.map_or(focusables.len() - 1, |index| {
+            (index + focusables.len() - 1) % focusables.len()
+        })
+    } else if let Some(direction) = input.navigate {
+        let Some(index) = current else {
+            state.focus = Some(focusables[0].0);
+            return;
+        };
+        let from = focusables[index].1.center();
+        // Prefer the closest widget in the direction that is the least off to the side.
+        let closest = focusables
+            .iter()
+            .enumerate()
+            .filter_map(|(other, &(_, rect))| {
+                let offset = rect.center() - from;
+                let along = offset.dot(direction);
+                let aside = offset.perp_dot(direction).abs();
+                (other != index && along > 0.).then_some((other, along + aside * 2.))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        match closest {
+            Some((other, _)) => other,
+            None => return,
+        }
+    } else {
+        return;
+    };
+    state.focus = Some(focusables[next].0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN_SIZE: Vec2 = Vec2::new(320., 180.);
+    const TEXTURE: TextureId = TextureId(0);
+
+    fn frame(
+        state: &mut UiState,
+        input: UiInput,
+        contents: impl FnOnce(&mut Ui),
+    ) -> Vec<UiCommand> {
+        let mut ui = Ui::new(state, input, SCREEN_SIZE);
+        contents(&mut ui);
+        ui.finish()
+    }
+
+    fn texture_dests(commands: &[UiCommand]) -> Vec<Rect> {
+        commands
+            .iter()
+            .filter_map(|command| match command {
+                UiCommand::Texture { dest, .. } => Some(*dest),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn buttons(ui: &mut Ui, count: usize) -> Vec<bool> {
+        (0..count)
+            .map(|_| ui.icon_button(TEXTURE, Vec2::splat(10.)))
+            .collect()
+    }
+
+    #[test]
+    fn layouts_place_the_widgets() {
+        let icon = Vec2::splat(10.);
+        let commands = frame(&mut UiState::default(), UiInput::default(), |ui| {
+            ui.vertical(Vec2::new(100., 40.), |ui| {
+                ui.icon(TEXTURE, icon);
+                ui.icon(TEXTURE, icon);
+            });
+            ui.horizontal(Vec2::new(100., 20.), |ui| {
+                ui.icon(TEXTURE, icon);
+                ui.icon(TEXTURE, icon);
+            });
+            // The icons fill the cells of the grid whatever size they ask for.
+            ui.grid(2, 2, Vec2::splat(30.), |ui| {
+                ui.icon(TEXTURE, icon);
+                ui.icon(TEXTURE, icon);
+                ui.icon(TEXTURE, icon);
+            });
+        });
+        assert_eq!(
+            texture_dests(&commands),
+            [
+                Rect::new(0., 0., 10., 10.),
+                Rect::new(0., 20., 10., 10.),
+                Rect::new(0., 50., 10., 10.),
+                Rect::new(20., 50., 10., 10.),
+                Rect::new(0., 80., 30., 30.),
+                Rect::new(40., 80., 30., 30.),
+                Rect::new(0., 120., 30., 30.),
+            ]
+        );
+    }
+
+    #[test]
+    fn focus_wraps_around_the_widgets() {
+        let mut state = UiState::default();
+        frame(&mut state, UiInput::default(), |ui| {
+            buttons(ui, 3);
+        });
+        let mut step = |input: UiInput| {
+            frame(&mut state, input, |ui| {
+                buttons(ui, 3);
+            });
+            state.focus
+        };
+        let next = UiInput {
+            next: true,
+            ..Default::default()
+        };
+        let previous = UiInput {
+            previous: true,
+            ..Default::default()
+        };
+        let down = UiInput {
+            navigate: Some(Vec2::Y),
+            ..Default::default()
+        };
+        assert_eq!(step(next), Some(WidgetId(0)));
+        assert_eq!(step(previous), Some(WidgetId(2)));
+        assert_eq!(step(next), Some(WidgetId(0)));
+        assert_eq!(step(down), Some(WidgetId(1)));
+        assert_eq!(step(down), Some(WidgetId(2)));
+        // Unlike the focus order, the directions don't wrap around.
+        assert_eq!(step(down), Some(WidgetId(2)));
+    }
+
+    #[test]
+    fn buttons_are_clicked_by_releasing_the_pointer_over_them() {
+        let click = |pressed_at: Vec2, released_at: Vec2| {
+            let mut state = UiState::default();
+            let mut is_clicked = false;
+            for (pointer, is_pressed, is_released) in
+                [(pressed_at, true, false), (released_at, false, true)]
+            {
+                let input = UiInput {
+                    pointer: Some(pointer),
+                    is_pressed,
+                    is_released,
+                    ..Default::default()
+                };
+                frame(&mut state, input, |ui| {
+                    ui.horizontal(SCREEN_SIZE, |ui| {
+                        ui.icon(TEXTURE, Vec2::splat(20.));
+                        // Placed at (30, 0) with the size of 30 by 30 including the borders.
+                        is_clicked = buttons(ui, 1)[0];
+                    });
+                });
+            }
+            is_clicked
+        };
+        let inside = Vec2::new(35., 5.);
+        let outside = Vec2::new(25., 5.);
+        assert!(click(inside, inside));
+        assert!(!click(inside, outside));
+        assert!(!click(outside, inside));
+    }
+}
@@ -0,0 +1,71 @@
+use macroquad::{
+    color::Color,
+    math::{Rect, Vec2},
+};
+
+use crate::{
+    Context,
+    game::{rendering::Sprite, ui::Ui},
+};
+
+const BACKDROP_COLOR: Color = Color::new(0., 0., 0., 0.5);
+const PAUSE_MENU_SIZE: Vec2 = Vec2::new(460., 430.);
+const ICON_SIZE: f32 = 48.;
+
+/// Builds the menu shown while the game is paused.
+pub fn pause_menu(ui: &mut Ui, context: &mut Context) {
+    ui.backdrop(BACKDROP_COLOR);
+    let screen = context.screen.size();
+    let rect = Rect::new(
+        (screen.x - PAUSE_MENU_SIZE.x) / 2.,
+        (screen.y - PAUSE_MENU_SIZE.y) / 2.,
+        PAUSE_MENU_SIZE.x,
+        PAUSE_MENU_SIZE.y,
+    );
+    let (player_texture, weapon_textures) = {
+        let sprites = context.world.borrow_pool::<Sprite>();
+        let texture = |entity| sprites.get(entity).unwrap().texture;
+        let weapons: Vec<_> = context
+            .player
+            .weapons
+            .iter()
+            .map(|weapon| (texture(weapon.entity), weapon.base))
+            .collect();
+        (texture(context.player.entity), weapons)
+    };
+    ui.panel(rect, |ui| {
+        ui.label("Paused");
+        ui.horizontal(Vec2::new(PAUSE_MENU_SIZE.x, ICON_SIZE), |ui| {
+            ui.icon(player_texture, Vec2::splat(ICON_SIZE));
+            let (level, _, _) = context.player.exp.level_progress();
+            ui.label(&format!(
+                "Level {level}, {} materials",
+                context.player.materials
+            ));
+        });
+        let columns = weapon_textures.len().max(1);
+        ui.grid(columns, 1, Vec2::splat(ICON_SIZE), |ui| {
+            for &(texture, weapon) in &weapon_textures {
+                ui.icon(texture, Vec2::splat(ICON_SIZE));
+                ui.tooltip(&format!(
+                    "Damage {}, crit {}",
+                    context.player.attack_damage(weapon),
+                    context.player.attack_crit_damage(weapon),
+                ));
+            }
+        });
+        if ui.button("Resume") {
+            context.is_paused = false;
+        }
+        ui.tooltip("Esc or Start");
+        let scaling_mode = context.screen.scaling_mode();
+        if ui.button(&format!("Scaling: {scaling_mode}")) {
+            context.screen.set_scaling_mode(scaling_mode.next());
+        }
+        ui.tooltip("How the game is fitted into the window (F2)");
+        if ui.button("Debug overlay") {
+            context.screen.toggle_debug_overlay();
+        }
+        ui.tooltip("F3");
+    });
+}
@@ -0,0 +1,505 @@
+use macroquad::{
+    color::Color,
+    input::{
+        KeyCode, MouseButton, is_key_down, is_key_pressed, is_mouse_button_down, mouse_position,
+        touches,
+    },
+    math::{Rect, Vec2},
+    prelude::ImageFormat,
+    texture::{FilterMode, Image},
+    time::get_frame_time,
+};
+
+use crate::{
+    ecs::entity::{Entity, World},
+    game::{
+        animation::{Animation, AnimationClip, ClipKind, PlaybackMode, animate_sprites},
+        bullet::{Bullet, move_bullets, test_bullet_collision},
+        camera::Camera,
+        capture::{FrameCapture, capture_frame, take_screenshot},
+        collision::{
+            Broadphase, Collider, Shape, SpatialIndex,
+            layer::{CollisionLayer, CollisionMask},
+            statics::{StaticGeometry, StaticShape},
+        },
+        combat_text::{CombatText, CombatTextPool, update_combat_texts},
+        enemy::{Corpse, Director, Enemy, enemy_contact_damage, move_enemies, unalive_enemies},
+        gamepad::{GamepadButton, Gamepads},
+        hp::Hp,
+        hud::{Hud, minimap_blips},
+        movement::MapConstraints,
+        particles::ParticleSystem,
+        player::{
+            Player,
+            attributes::{Attr, Attrs},
+            exp::Exp,
+            player_autoattack, player_move,
+            weapons::{DamageModifier, Weapon, WeaponBase, weapon_offset},
+        },
+        rendering::{
+            Frame, ScalingMode, Screen, Sprite, SpriteSource, draw_list::SortMode,
+            textures::TextureId, update_sprite_flashes,
+        },
+        tilemap::Tilemap,
+        transform::Transform,
+        trigger::{Trigger, detect_triggers, move_triggers},
+        ui::{Ui, UiInput, UiState, menus::pause_menu},
+    },
+};
+
+pub mod ecs;
+pub mod game;
+
+const ASSET_BAKED_ENEMY_BYTES: &[u8] = include_bytes!("../assets/Destructoid.png");
+const ASSET_BAKED_GUN_BYTES: &[u8] = include_bytes!("../assets/Nailgun.png");
+const ASSET_BAKED_BULLET_BYTES: &[u8] = include_bytes!("../assets/NailgunBullet.png");
+const ASSET_BAKED_PLAYER_BYTES: &[u8] = include_bytes!("../assets/Ted.png");
+const ASSET_BAKED_TILESET_BYTES: &[u8] = include_bytes!("../assets/Tileset.png");
+const ASSET_BAKED_ARENA_MAP: &str = include_str!("../assets/Arena.map");
+
+pub async fn setup_context(
+    broadphase: Broadphase,
+    resolution: (u32, u32),
+    scaling_mode: ScalingMode,
+    capture_every: Option<u32>,
+) -> Context {
+    let mut world = World::new();
+    world.register_type::<Transform>();
+    world.register_type::<Collider>();
+    world.register_type::<Sprite>();
+    world.register_type::<Hp>();
+    world.register_type::<Enemy>();
+    world.register_type::<Corpse>();
+    world.register_type::<Bullet>();
+    world.register_type::<Trigger>();
+    world.register_type::<Animation>();
+    world.register_type::<CombatText>();
+    world.register_type::<Tilemap>();
+    let mut screen = Screen::new(resolution.0, resolution.1);
+    screen.set_scaling_mode(scaling_mode);
+    screen.update_layout();
+    // Ted and the enemies share the layer, so the ones standing lower must be drawn in front.
+    screen.set_sort_mode(1, SortMode::Y);
+    let collisions = broadphase.build();
+
+    let ted_texture =
+        screen.add_texture(&baked_image(ASSET_BAKED_PLAYER_BYTES), FilterMode::Linear);
+    let ted = world.create_entity();
+    {
+        let mut transforms = world.borrow_pool_mut::<Transform>();
+        let mut colliders = world.borrow_pool_mut::<Collider>();
+        let mut sprites = world.borrow_pool_mut::<Sprite>();
+        let mut hps = world.borrow_pool_mut::<Hp>();
+        let mut animations = world.borrow_pool_mut::<Animation>();
+        animations.insert(ted, character_animation(screen.texture_size(ted_texture)));
+        transforms.insert(
+            ted,
+            Transform {
+                position: Vec2::ZERO,
+                rotation: 0.,
+            },
+        );
+        colliders.insert(
+            ted,
+            Collider {
+                shape: Shape::Circle { radius: 30. },
+                monitoring: CollisionMask::NONE,
+                monitorable: CollisionLayer::Player.mask(),
+                body: None,
+            },
+        );
+        sprites.insert(
+            ted,
+            Sprite::new(ted_texture, SpriteSource::World(Vec2::splat(60.)), 1),
+        );
+        screen.add_sprite(ted, &sprites);
+        hps.insert(ted, Hp(10));
+    }
+    let hurtbox = world.create_entity();
+    {
+        let mut transforms = world.borrow_pool_mut::<Transform>();
+        let mut colliders = world.borrow_pool_mut::<Collider>();
+        let mut triggers = world.borrow_pool_mut::<Trigger>();
+        transforms.insert(
+            hurtbox,
+            Transform {
+                position: Vec2::ZERO,
+                rotation: 0.,
+            },
+        );
+        colliders.insert(
+            hurtbox,
+            Collider {
+                shape: Shape::Circle { radius: 30. },
+                monitoring: CollisionLayer::Enemy.mask(),
+                monitorable: CollisionLayer::Trigger.mask(),
+                body: None,
+            },
+        );
+        triggers.insert(hurtbox, Trigger::new(Some(ted)));
+    }
+    let bullet_texture =
+        screen.add_texture(&baked_image(ASSET_BAKED_BULLET_BYTES), FilterMode::Linear);
+    let enemy_texture =
+        screen.add_texture(&baked_image(ASSET_BAKED_ENEMY_BYTES), FilterMode::Linear);
+    let enemy_animation = character_animation(screen.texture_size(enemy_texture));
+    let gun_texture = screen.add_texture(&baked_image(ASSET_BAKED_GUN_BYTES), FilterMode::Linear);
+    let player = Player {
+        entity: ted,
+        hurtbox,
+        attrs: Attrs::from_attr(Attr::Health, 10),
+        exp: Exp(0),
+        materials: 0,
+        invunerable_until: 0.,
+        weapons: {
+            let mut res = Vec::new();
+            for i in 0..4 {
+                let entity = world.create_entity();
+                {
+                    let offset = weapon_offset(i, 4);
+                    let mut sprites = world.borrow_pool_mut::<Sprite>();
+                    let mut transforms = world.borrow_pool_mut::<Transform>();
+                    sprites.insert(
+                        entity,
+                        Sprite::new(gun_texture, SpriteSource::World(Vec2::splat(30.)), 2),
+                    );
+                    transforms.insert(
+                        entity,
+                        Transform {
+                            position: offset,
+                            rotation: offset.to_angle() - std::f32::consts::PI,
+                        },
+                    );
+                    screen.add_sprite(entity, &sprites);
+                }
+                let default_weapon = Weapon {
+                    entity,
+                    base: WeaponBase {
+                        bonus_attrs: Attrs::from_attr(Attr::Speed, 75),
+                        crit_mult: 2.,
+                        damage_mod: DamageModifier {
+                            attr: Attr::Damage,
+                            multiplier: 1.,
+                        },
+                    },
+                    next_shot_time: 0.,
+                };
+                res.push(default_weapon);
+            }
+            res
+        },
+    };
+    let map = world.create_entity();
+    let tilemap = {
+        // The tiles are tiny, so the linear filtering would blur them into their neighbours.
+        let tileset =
+            screen.add_texture(&baked_image(ASSET_BAKED_TILESET_BYTES), FilterMode::Nearest);
+        let size = screen.texture_size(tileset);
+        Tilemap::parse(ASSET_BAKED_ARENA_MAP, tileset, size, 0)
+    };
+    let map_origin = Vec2::splat(-600.);
+    let rect = tilemap.bounds(map_origin);
+    let map_constraints = MapConstraints {
+        max: rect.point() + rect.size(),
+        min: rect.point(),
+    };
+    let obstacles = vec![
+        StaticShape::Rect(Rect::new(-300., -40., 80., 80.)),
+        StaticShape::Rect(Rect::new(220., -40., 80., 80.)),
+        StaticShape::polygon([
+            Vec2::new(0., 200.),
+            Vec2::new(60., 300.),
+            Vec2::new(-60., 300.),
+        ]),
+    ];
+    let static_geometry = {
+        let mut shapes = tilemap.solid_shapes(map_origin);
+        shapes.extend(obstacles.iter().cloned());
+        StaticGeometry::new(shapes, STATIC_GEOMETRY_CELL_SIZE)
+    };
+    {
+        let mut transforms = world.borrow_pool_mut::<Transform>();
+        let mut tilemaps = world.borrow_pool_mut::<Tilemap>();
+        transforms.insert(
+            map,
+            Transform {
+                position: map_origin,
+                rotation: 0.,
+            },
+        );
+        tilemaps.insert(map, tilemap);
+    }
+    let camera = {
+        let mut res = Camera::new(screen.size(), Vec2::ZERO);
+        res.bounds = Some(rect);
+        res
+    };
+    let director = Some(Director {
+        next_batch_time: 0.,
+        wave: 0,
+        pending_spawns: Vec::new(),
+    });
+    screen.build_textures();
+    Context {
+        world,
+        screen,
+        camera,
+        capture: {
+            let mut res = FrameCapture::new(capture_every.unwrap_or(FRAME_CAPTURE_EVERY));
+            if capture_every.is_some() {
+                res.toggle_recording();
+            }
+            res
+        },
+        combat_texts: CombatTextPool::new(),
+        particles: ParticleSystem::new(PARTICLE_BUDGET, PARTICLE_LAYER),
+        collisions,
+        player,
+        map_constraints,
+        static_geometry,
+        obstacles,
+        contacts: Vec::new(),
+        director,
+        bullet_texture,
+        enemy_texture,
+        enemy_animation,
+        gamepads: Gamepads::new(),
+        ui_state: UiState::default(),
+        is_paused: false,
+        time: 0.,
+    }
+}
+
+/// Decodes the PNG baked into the executable.
+fn baked_image(bytes: &[u8]) -> Image {
+    Image::from_file_with_format(bytes, Some(ImageFormat::Png))
+        .expect("Baked image must be a valid PNG!")
+}
+
+/// Constructs the animation with the clips of a character from the size of its texture.
+///
+/// The baked textures only have a single frame, so every clip shows the whole texture.
+fn character_animation(texture_size: Vec2) -> Animation {
+    let mut res = Animation::from_grid(texture_size, 1, 1);
+    let clip = |frame_duration, mode| AnimationClip {
+        frames: 0..1,
+        frame_duration,
+        mode,
+    };
+    res.set_clip(ClipKind::Idle, clip(0.2, PlaybackMode::Loop));
+    res.set_clip(ClipKind::Walk, clip(0.1, PlaybackMode::Loop));
+    res.set_clip(
+        ClipKind::Hurt,
+        clip(0.25, PlaybackMode::Then(ClipKind::Idle)),
+    );
+    res.set_clip(ClipKind::Death, clip(0.5, PlaybackMode::Once));
+    res
+}
+
+pub struct Context {
+    world: World,
+    screen: Screen,
+    camera: Camera,
+    capture: FrameCapture,
+    combat_texts: CombatTextPool,
+    particles: ParticleSystem,
+    player: Player,
+    collisions: Box<dyn SpatialIndex>,
+    map_constraints: MapConstraints,
+    static_geometry: StaticGeometry,
+    /// The hand-placed shapes of the `static_geometry`, which are drawn over the tiles.
+    obstacles: Vec<StaticShape>,
+    /// A scratch buffer for the contacts between the colliders pushed apart every tick.
+    contacts: Vec<(Entity, Entity)>,
+    director: Option<Director>,
+    bullet_texture: TextureId,
+    enemy_texture: TextureId,
+    enemy_animation: Animation,
+    gamepads: Gamepads,
+    ui_state: UiState,
+    is_paused: bool,
+    /// The time the simulation has been running for in seconds.
+    ///
+    /// It only advances in [`Context::fixed_update`], so the timers based on it
+    /// (e.g. the waves, the weapon cooldowns or the invulnerability) stop during the pause.
+    time: f32,
+}
+
+impl Context {
+    pub fn fixed_update(&mut self) {
+        if self.is_paused {
+            return;
+        }
+        self.time += FIXED_DELTA;
+        move_enemies(self);
+        move_bullets(self);
+        {
+            // 😭😭😭
+            let mut tmp = self.director.take().unwrap();
+            tmp.spawn_enemies(self);
+            self.director = Some(tmp);
+        }
+        move_triggers(self);
+        {
+            let mut transforms = self.world.borrow_pool_mut::<Transform>();
+            let colliders = self.world.borrow_pool::<Collider>();
+
+            let c = &self.map_constraints;
+            let crect = Rect::new(c.min.x, c.min.y, (c.max - c.min).x, (c.max - c.min).y);
+            self.collisions.update(&transforms, &colliders, crect);
+            let static_geometry = &self.static_geometry;
+            self.collisions.resolve_overlaps(
+                &mut transforms,
+                &colliders,
+                COLLISION_RESPONSE_ITERATIONS,
+                &mut self.contacts,
+                &|collider, position| {
+                    static_geometry
+                        .push_out(collider, position)
+                        .clamp(c.min, c.max)
+                },
+            );
+            // The triggers and the bullets must see where the bodies have been pushed to.
+            self.collisions.update(&transforms, &colliders, crect);
+        }
+        detect_triggers(self);
+        player_autoattack(self);
+        enemy_contact_damage(self);
+        test_bullet_collision(self);
+        unalive_enemies(self);
+    }
+    pub fn update(&mut self) {
+        self.gamepads.update();
+        if is_key_pressed(KeyCode::Escape) || self.gamepads.is_pressed(GamepadButton::Start) {
+            self.is_paused = !self.is_paused;
+        }
+        if !self.is_paused {
+            animate_sprites(self, get_frame_time());
+            update_combat_texts(self, get_frame_time());
+            self.particles.update(get_frame_time());
+            update_sprite_flashes(self, get_frame_time());
+        }
+        let pos = {
+            let transforms = self.world.borrow_pool_mut::<Transform>();
+            transforms.get(self.player.entity).unwrap().position
+        };
+        if is_key_pressed(KeyCode::Equal) {
+            self.camera.set_zoom(self.camera.zoom() * CAMERA_ZOOM_STEP);
+        }
+        if is_key_pressed(KeyCode::Minus) {
+            self.camera.set_zoom(self.camera.zoom() / CAMERA_ZOOM_STEP);
+        }
+        self.screen.update_layout();
+        self.camera.set_view_size(self.screen.size());
+        self.camera.update(pos, get_frame_time());
+        let hud = {
+            let hps = self.world.borrow_pool_mut::<Hp>();
+            let c = &self.map_constraints;
+            let director = self.director.as_ref().unwrap();
+            let (level, exp, exp_required) = self.player.exp.level_progress();
+            Hud {
+                hp: hps.get(self.player.entity).unwrap().0,
+                max_hp: self.player.max_hp(),
+                level,
+                exp,
+                exp_required,
+                wave: director.wave,
+                wave_time_left: director.next_batch_time - self.time,
+                materials: self.player.materials,
+                arena: Rect::new(c.min.x, c.min.y, (c.max - c.min).x, (c.max - c.min).y),
+                view: self.camera.view_rect(),
+                blips: minimap_blips(self),
+            }
+        };
+        let ui = {
+            let mut state = std::mem::take(&mut self.ui_state);
+            let mut ui = Ui::new(
+                &mut state,
+                UiInput::poll(&self.screen, &self.gamepads),
+                self.screen.size(),
+            );
+            if self.is_paused {
+                pause_menu(&mut ui, self);
+            }
+            let res = ui.finish();
+            self.ui_state = state;
+            res
+        };
+        self.screen.render_sprites(&Frame {
+            target: self.camera.view_center(),
+            zoom: Vec2::splat(self.camera.zoom()),
+            background: Color::from_hex(0xffb30f),
+            sprites: &self.world.borrow_pool::<Sprite>(),
+            transforms: &self.world.borrow_pool::<Transform>(),
+            tilemaps: &self.world.borrow_pool::<Tilemap>(),
+            colliders: &self.world.borrow_pool::<Collider>(),
+            obstacles: &self.obstacles,
+            collisions: self.collisions.as_ref(),
+            combat_texts: &self.world.borrow_pool::<CombatText>(),
+            particles: &self.particles,
+            hud: &hud,
+            ui: &ui,
+        });
+        let delta = {
+            let up = (is_key_down(KeyCode::W) || is_key_down(KeyCode::Up)) as i32 as f32;
+            let left = (is_key_down(KeyCode::A) || is_key_down(KeyCode::Left)) as i32 as f32;
+            let down = (is_key_down(KeyCode::S) || is_key_down(KeyCode::Down)) as i32 as f32;
+            let right = (is_key_down(KeyCode::D) || is_key_down(KeyCode::Right)) as i32 as f32;
+            let mut dir =
+                (up * Vec2::NEG_Y + down * Vec2::Y + left * Vec2::NEG_X + right * Vec2::X)
+                    .normalize_or_zero();
+            // Without a keyboard (e.g. on the phones) Ted walks towards the held pointer.
+            if dir == Vec2::ZERO
+                && (is_mouse_button_down(MouseButton::Left) || !touches().is_empty())
+            {
+                const POINTER_DEAD_ZONE: f32 = 8.;
+                let pos = {
+                    let transforms = self.world.borrow_pool::<Transform>();
+                    transforms.get(self.player.entity).unwrap().position
+                };
+                let offset = self.pointer_world_position() - pos;
+                if offset.length() > POINTER_DEAD_ZONE {
+                    dir = offset.normalize();
+                }
+            }
+            const WALK_SPEED: f32 = 160.;
+            dir * WALK_SPEED * get_frame_time()
+        };
+        if !self.is_paused {
+            player_move(self, delta); // TODO: get input from player...
+        }
+        if is_key_pressed(KeyCode::F3) {
+            self.screen.toggle_debug_overlay();
+        }
+        capture_frame(self);
+        if is_key_pressed(KeyCode::F9) {
+            take_screenshot(self);
+        }
+        if is_key_pressed(KeyCode::F10) {
+            self.capture.toggle_recording();
+        }
+        if is_key_pressed(KeyCode::F2) {
+            self.screen
+                .set_scaling_mode(self.screen.scaling_mode().next());
+        }
+    }
+    /// Returns the position in the world under the first touch or under the mouse if there are no touches.
+    pub fn pointer_world_position(&self) -> Vec2 {
+        let position = touches()
+            .first()
+            .map_or_else(|| mouse_position().into(), |touch| touch.position);
+        self.camera
+            .screen_to_world(self.screen.window_to_screen(position))
+    }
+}
+
+pub const FIXED_DELTA: f32 = 1. / 30.;
+pub const FIXED_STEPS_MAX: u32 = 4;
+pub const COLLISION_RESPONSE_ITERATIONS: usize = 2;
+pub const STATIC_GEOMETRY_CELL_SIZE: f32 = 100.;
+pub const CAMERA_ZOOM_STEP: f32 = 1.25;
+/// The largest number of live particles, kept low for the web builds on weak devices.
+pub const PARTICLE_BUDGET: usize = 2000;
+pub const PARTICLE_LAYER: u8 = 3;
+/// Every which frame is dumped by the frame capture unless specified at startup.
+pub const FRAME_CAPTURE_EVERY: u32 = 5;
@@ -12,6 +12,7 @@ result = subprocess.run([
     "cargo", "build",
     "--manifest-path", "../../Cargo.toml",
     "--target", target,
+    "--bin", "last_stand",
     "--release"
 ])
 if result.returncode != 0:
@@ -27,11 +28,15 @@ with open("./templates/injector.js", "r") as file:
 with open("./templates/macroquad.js", "r") as file:
     macroquad = file.read()
 
+with open("./templates/capture.js", "r") as file:
+    capture = file.read()
+
 with open("./templates/template.html", "r") as file:
     html = file.read()
 
 result = html\
     .replace("INSERT_MACROQUAD_HERE", macroquad)\
+    .replace("INSERT_PLUGINS_HERE", capture)\
     .replace("INSERT_INJECTOR_HERE", injector.replace("INSERT_WASM_HERE", wasm))
 
 with open("../../target/last_stand.html", "w") as file:
@@ -0,0 +1,17 @@
+// Lets the game download the screenshots and the captured frames (see `src/game/capture.rs`).
+miniquad_add_plugin({
+    register_plugin: function (importObject) {
+        importObject.env.last_stand_download = function (name, nameLen, data, dataLen) {
+            const memory = new Uint8Array(wasm_memory.buffer);
+            const fileName = new TextDecoder().decode(memory.slice(name, name + nameLen));
+            const blob = new Blob([memory.slice(data, data + dataLen)], { type: "image/png" });
+            const link = document.createElement("a");
+            link.href = URL.createObjectURL(blob);
+            link.download = fileName;
+            link.click();
+            URL.revokeObjectURL(link.href);
+        };
+    },
+    name: "last_stand_capture",
+    version: 1,
+});